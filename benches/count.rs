@@ -0,0 +1,48 @@
+//! Compares `count_tokens` against `encode(...).len()`,
+//! and times a single long pretoken, whose merges must not rescan all of its symbols.
+//!
+//! ```sh
+//! cargo bench --bench count
+//! ```
+use gptbpe::bpe;
+use rand::{Rng, SeedableRng};
+use std::hint::black_box;
+use std::time::Instant;
+
+const ITERATIONS: usize = 20;
+
+/// Length in bytes of the single pretoken.
+const PRETOKEN: usize = 50_000;
+
+fn main() {
+    let text = std::fs::read("README.md").unwrap().repeat(50);
+    let lookup = &bpe::vocabulary::R50K_TOKENS;
+    assert_eq!(bpe::count_tokens(&text, lookup), bpe::encode(&text, lookup).len());
+
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        black_box(bpe::encode(black_box(&text), lookup).len());
+    }
+    let encode = start.elapsed() / ITERATIONS as u32;
+
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        black_box(bpe::count_tokens(black_box(&text), lookup));
+    }
+    let count = start.elapsed() / ITERATIONS as u32;
+
+    println!("[INFO][BENCH]: {} bytes", text.len());
+    println!("[INFO][BENCH]: encode(...).len() -> {:?}", encode);
+    println!("[INFO][BENCH]: count_tokens(...) -> {:?}", count);
+
+    // Letters without spaces are one pretoken, merged by a single encoder.
+    let mut rng = rand::rngs::StdRng::seed_from_u64(0);
+    let word: Vec<u8> = (0..PRETOKEN).map(|_| rng.gen_range(b'a'..=b'z')).collect();
+    assert_eq!(bpe::count_tokens(&word, lookup), bpe::encode(&word, lookup).len());
+
+    let start = Instant::now();
+    black_box(bpe::encode(black_box(&word), lookup).len());
+    let pretoken = start.elapsed();
+
+    println!("[INFO][BENCH]: encode(...) of a {} byte pretoken -> {:?}", word.len(), pretoken);
+}
@@ -13,7 +13,7 @@ const SYMBOLS = {
     },
     decode_p50k: {
         args: ["buffer", "u32", "function"],
-        returns: "void",
+        returns: "bool",
     },
     encode_r50k: {
         args: ["buffer", "u32", "function"],
@@ -21,7 +21,7 @@ const SYMBOLS = {
     },
     decode_r50k: {
         args: ["buffer", "u32", "function"],
-        returns: "void",
+        returns: "bool",
     },
     encode_cl100k: {
         args: ["buffer", "u32", "function"],
@@ -29,8 +29,32 @@ const SYMBOLS = {
     },
     decode_cl100k: {
         args: ["buffer", "u32", "function"],
+        returns: "bool",
+    },
+    encode_offsets_p50k: {
+        args: ["buffer", "u32", "bool", "function"],
+        returns: "void",
+    },
+    encode_offsets_r50k: {
+        args: ["buffer", "u32", "bool", "function"],
         returns: "void",
     },
+    encode_offsets_cl100k: {
+        args: ["buffer", "u32", "bool", "function"],
+        returns: "void",
+    },
+    count_p50k: {
+        args: ["buffer", "usize"],
+        returns: "usize",
+    },
+    count_r50k: {
+        args: ["buffer", "usize"],
+        returns: "usize",
+    },
+    count_cl100k: {
+        args: ["buffer", "usize"],
+        returns: "usize",
+    },
 } as const;
 
 type SimplePointer = Array<{
@@ -65,13 +89,22 @@ export function grapheme(buffer: Uint8Array): Uint8Array {
 
 type Vocabulary = 'r50k' | 'p50k' | 'cl100k';
 
+// Token ids cross the C ABI as `u16` for r50k and p50k, and as `u32` for cl100k whose ids go up to 100255.
+function width(vocabulary: Vocabulary): "u16" | "u32" {
+    return vocabulary === 'cl100k' ? "u32" : "u16";
+}
+
+// Copies ids into the array type the `decode_*` symbol reads, so it never reads past the buffer.
+function ids(buffer: Uint16Array | Uint32Array, vocabulary: Vocabulary): Uint16Array | Uint32Array {
+    return vocabulary === 'cl100k' ? Uint32Array.from(buffer) : Uint16Array.from(buffer);
+}
 
-export function encode(buffer: Uint8Array, vocabulary: Vocabulary): Uint16Array {
-    const pointer: SimplePointer = Array(buffer.length);
+export function encode(buffer: Uint8Array, vocabulary: Vocabulary): Uint32Array {
+    const pointer: SimplePointer = [];
     const callback = new JSCallback(function (idx: bigint, value: number): void {
         pointer.push({ idx, value })
     }, {
-        args: ["usize", "u32"],
+        args: ["usize", width(vocabulary)],
         returns: 'void'
     });
 
@@ -79,7 +112,7 @@ export function encode(buffer: Uint8Array, vocabulary: Vocabulary): Uint16Array
 
     switch (vocabulary) {
         case 'p50k':
-            DYLIB.symbols.decode_p50k(
+            DYLIB.symbols.encode_p50k(
                 buffer,
                 buffer.length,
                 callback
@@ -87,7 +120,7 @@ export function encode(buffer: Uint8Array, vocabulary: Vocabulary): Uint16Array
             break;
 
         case 'r50k':
-            DYLIB.symbols.decode_r50k(
+            DYLIB.symbols.encode_r50k(
                 buffer,
                 buffer.length,
                 callback
@@ -95,14 +128,14 @@ export function encode(buffer: Uint8Array, vocabulary: Vocabulary): Uint16Array
             break;
 
         case 'cl100k':
-            DYLIB.symbols.decode_cl100k(
+            DYLIB.symbols.encode_cl100k(
                 buffer,
                 buffer.length,
                 callback
             );   
             break;
         default:
-            DYLIB.symbols.decode_p50k(
+            DYLIB.symbols.encode_p50k(
                 buffer,
                 buffer.length,
                 callback
@@ -111,62 +144,84 @@ export function encode(buffer: Uint8Array, vocabulary: Vocabulary): Uint16Array
     }
     DYLIB.close();
 
-    return Uint16Array.from(
+    return Uint32Array.from(
         pointer
-            // // See https://developer.mozilla.org/en-US/docs/Web/JavaScript/Reference/Global_Objects/BigInt#comparisons for sorting bigint
-            // .sort((a, b) => (a.idx < b.idx) ? -1 : ((a.idx > b.idx) ? 1 : 0))
+            // See https://developer.mozilla.org/en-US/docs/Web/JavaScript/Reference/Global_Objects/BigInt#comparisons for sorting bigint
+            .sort((a, b) => (a.idx < b.idx) ? -1 : ((a.idx > b.idx) ? 1 : 0))
             .map((v, _index, _array) => v.value)
     )
 };
 
-export function decode(buffer: Uint16Array, vocabulary: Vocabulary): Uint8Array {
+export function count(buffer: Uint8Array, vocabulary: Vocabulary): number {
+    const DYLIB = dlopen(FOREIGN_INTERFACE, SYMBOLS);
+
+    let tokens: number | bigint;
+    switch (vocabulary) {
+        case 'r50k':
+            tokens = DYLIB.symbols.count_r50k(buffer, buffer.length);
+            break;
+
+        case 'cl100k':
+            tokens = DYLIB.symbols.count_cl100k(buffer, buffer.length);
+            break;
+
+        default:
+            tokens = DYLIB.symbols.count_p50k(buffer, buffer.length);
+            break;
+    }
+    DYLIB.close();
+
+    return Number(tokens)
+};
+
+export function decode(buffer: Uint16Array | Uint32Array, vocabulary: Vocabulary): Uint8Array {
     const pointer: SimplePointer = [];
     const callback = new JSCallback(function (idx: bigint, value: number): void {
         pointer.push({ idx, value })
     }, {
-        args: ["usize", "u8"],
+        args: ["usize", vocabulary === 'cl100k' ? "u16" : "u8"],
         returns: "void"
     });
+    const tokens = ids(buffer, vocabulary);
     const DYLIB = dlopen(FOREIGN_INTERFACE, SYMBOLS);
 
+    let decoded = false;
     switch (vocabulary) {
         case 'p50k':
-            DYLIB.symbols.decode_p50k(
-                buffer,
-                buffer.length,
+            decoded = DYLIB.symbols.decode_p50k(
+                tokens,
+                tokens.length,
                 callback
             );   
             break;
 
         case 'r50k':
-            DYLIB.symbols.decode_r50k(
-                buffer,
-                buffer.length,
+            decoded = DYLIB.symbols.decode_r50k(
+                tokens,
+                tokens.length,
                 callback
             );   
             break;
 
         case 'cl100k':
-            DYLIB.symbols.decode_cl100k(
-                buffer,
-                buffer.length,
+            decoded = DYLIB.symbols.decode_cl100k(
+                tokens,
+                tokens.length,
                 callback
             );   
             break;
         default:
-            DYLIB.symbols.decode_p50k(
-                buffer,
-                buffer.length,
+            decoded = DYLIB.symbols.decode_p50k(
+                tokens,
+                tokens.length,
                 callback
             );  
             break;
     }
-    DYLIB.symbols.decode_p50k(
-        buffer,
-        buffer.length,
-        callback
-    )
     DYLIB.close();
+    if (!decoded) {
+        throw new RangeError("[ERROR]: Token id is not in the vocabulary.");
+    }
     return Uint8Array.from(
         pointer
             // See https://developer.mozilla.org/en-US/docs/Web/JavaScript/Reference/Global_Objects/BigInt#comparisons for sorting bigint
@@ -175,6 +230,60 @@ export function decode(buffer: Uint16Array, vocabulary: Vocabulary): Uint8Array
     )
 };
 
+type Offset = {
+    token: number
+    start: number
+    end: number
+}
+
+// Offsets are UTF-16 code units, so `text.slice(start, end)` highlights the token in the JavaScript string.
+export function encodeWithOffsets(buffer: Uint8Array, vocabulary: Vocabulary): Array<Offset> {
+    const pointer: Array<{ idx: bigint } & Offset> = [];
+    const callback = new JSCallback(function (idx: bigint, token: number, start: bigint, end: bigint): void {
+        pointer.push({ idx, token, start: Number(start), end: Number(end) })
+    }, {
+        args: ["usize", width(vocabulary), "usize", "usize"],
+        returns: "void"
+    });
+
+    const DYLIB = dlopen(FOREIGN_INTERFACE, SYMBOLS);
+
+    switch (vocabulary) {
+        case 'r50k':
+            DYLIB.symbols.encode_offsets_r50k(
+                buffer,
+                buffer.length,
+                true,
+                callback
+            );
+            break;
+
+        case 'cl100k':
+            DYLIB.symbols.encode_offsets_cl100k(
+                buffer,
+                buffer.length,
+                true,
+                callback
+            );
+            break;
+
+        default:
+            DYLIB.symbols.encode_offsets_p50k(
+                buffer,
+                buffer.length,
+                true,
+                callback
+            );
+            break;
+    }
+    DYLIB.close();
+
+    return pointer
+        // See https://developer.mozilla.org/en-US/docs/Web/JavaScript/Reference/Global_Objects/BigInt#comparisons for sorting bigint
+        .sort((a, b) => (a.idx < b.idx) ? -1 : ((a.idx > b.idx) ? 1 : 0))
+        .map(({ token, start, end }) => ({ token, start, end }))
+};
+
 async function *readLines(path: string) {
     const reader = Bun.file(path).stream().pipeThrough(new TextDecoderStream('utf-8')).getReader();
     let remainder = ''
@@ -195,6 +304,12 @@ async function *readLines(path: string) {
 }
 
 import { equal, deepEqual } from "bun:assert";
+
+// cl100k ids above 65535 must survive both directions of the C ABI.
+deepEqual(encode(new TextEncoder().encode(" mouths"), 'cl100k'), Uint32Array.from([65609]));
+equal(new TextDecoder().decode(decode(Uint32Array.from([65609]), 'cl100k')), " mouths");
+equal(count(new TextEncoder().encode("hello world 👋"), 'cl100k'), 4);
+
 const path = "./bpeRanks/TestPlans.txt";
 let encodingname: Vocabulary | undefined;
 let sample = "";
@@ -227,8 +342,9 @@ for await(const line of readLines(path)) {
         })
 
         const encoding = encode(new TextEncoder().encode(sample), encodingname as Vocabulary);
-        deepEqual(encoding, Uint16Array.from(encoded))
-        const decoding = new TextDecoder().decode(decode(Uint16Array.from(encoded), encodingname as Vocabulary));
+        deepEqual(encoding, Uint32Array.from(encoded))
+        equal(count(new TextEncoder().encode(sample), encodingname as Vocabulary), encoded.length)
+        const decoding = new TextDecoder().decode(decode(Uint32Array.from(encoded), encodingname as Vocabulary));
         equal(sample, decoding)
 
     }
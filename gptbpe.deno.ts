@@ -18,7 +18,7 @@ const SYMBOLS = {
     },
     decode_p50k: {
         parameters: ["buffer", "u32", "function"],
-        result: "void",
+        result: "bool",
     },
     encode_r50k: {
         parameters: ["buffer", "u32", "function"],
@@ -26,7 +26,7 @@ const SYMBOLS = {
     },
     decode_r50k: {
         parameters: ["buffer", "u32", "function"],
-        result: "void",
+        result: "bool",
     },
     encode_cl100k: {
         parameters: ["buffer", "u32", "function"],
@@ -34,8 +34,32 @@ const SYMBOLS = {
     },
     decode_cl100k: {
         parameters: ["buffer", "u32", "function"],
+        result: "bool",
+    },
+    encode_offsets_p50k: {
+        parameters: ["buffer", "u32", "bool", "function"],
+        result: "void",
+    },
+    encode_offsets_r50k: {
+        parameters: ["buffer", "u32", "bool", "function"],
         result: "void",
     },
+    encode_offsets_cl100k: {
+        parameters: ["buffer", "u32", "bool", "function"],
+        result: "void",
+    },
+    count_p50k: {
+        parameters: ["buffer", "usize"],
+        result: "usize",
+    },
+    count_r50k: {
+        parameters: ["buffer", "usize"],
+        result: "usize",
+    },
+    count_cl100k: {
+        parameters: ["buffer", "usize"],
+        result: "usize",
+    },
 } as const;
 
 type SimplePointer = Array <{
@@ -45,11 +69,21 @@ type SimplePointer = Array <{
 
 type vocabulary = 'r50k' | 'p50k' | 'cl100k';
 
-export function encode (buffer: Uint8Array, vocabulary?: vocabulary): Uint16Array{
+// Token ids cross the C ABI as `u16` for r50k and p50k, and as `u32` for cl100k whose ids go up to 100255.
+function width (vocabulary?: vocabulary): "u16" | "u32" {
+    return vocabulary === 'cl100k' ? "u32" : "u16";
+}
+
+// Copies ids into the array type the `decode_*` symbol reads, so it never reads past the buffer.
+function ids (buffer: Uint16Array | Uint32Array, vocabulary?: vocabulary): Uint16Array | Uint32Array {
+    return vocabulary === 'cl100k' ? Uint32Array.from(buffer) : Uint16Array.from(buffer);
+}
+
+export function encode (buffer: Uint8Array, vocabulary?: vocabulary): Uint32Array{
     const pointer: SimplePointer = [];
 
     const callback = new Deno.UnsafeCallback({
-        parameters: ["usize", "u16"],
+        parameters: ["usize", width(vocabulary)],
         result: "void"
     }, function (idx: bigint, value: number): void {
         pointer.push({idx, value})
@@ -89,7 +123,7 @@ export function encode (buffer: Uint8Array, vocabulary?: vocabulary): Uint16Arra
     }
     DYLIB.close();
 
-    return Uint16Array.from(
+    return Uint32Array.from(
         pointer
         // See https://developer.mozilla.org/en-US/docs/Web/JavaScript/Reference/Global_Objects/BigInt#comparisons for sorting bigint
         .sort((a, b) => (a.idx < b.idx) ? -1 : ((a.idx > b.idx) ? 1 : 0))
@@ -97,55 +131,133 @@ export function encode (buffer: Uint8Array, vocabulary?: vocabulary): Uint16Arra
     )
 };
 
-export function decode (buffer: Uint16Array, _vocabulary: vocabulary): Uint8Array {
+export function count (buffer: Uint8Array, vocabulary?: vocabulary): number {
+    const DYLIB = Deno.dlopen(FOREIGN_INTERFACE, SYMBOLS);
+    let tokens: number | bigint;
+    switch (vocabulary) {
+        case 'r50k':
+            tokens = DYLIB.symbols.count_r50k(buffer, buffer.length)
+            break;
+        case 'cl100k':
+            tokens = DYLIB.symbols.count_cl100k(buffer, buffer.length)
+            break;
+
+        default:
+            tokens = DYLIB.symbols.count_p50k(buffer, buffer.length)
+            break;
+    }
+    DYLIB.close();
+
+    return Number(tokens)
+};
+
+export function decode (buffer: Uint16Array | Uint32Array, vocabulary?: vocabulary): Uint8Array {
     const pointer: SimplePointer = [];
 
     const callback = new Deno.UnsafeCallback({
-        parameters: ["usize", "u16"],
+        parameters: ["usize", vocabulary === 'cl100k' ? "u16" : "u8"],
         result: "void"
     }, (idx: bigint, value: number): void => {
         pointer.push({idx, value})
     });
 
+    const tokens = ids(buffer, vocabulary);
     const DYLIB = Deno.dlopen(FOREIGN_INTERFACE, SYMBOLS);
+    let decoded = false;
     switch (vocabulary) {
         case 'p50k':
-            DYLIB.symbols.decode_p50k(
-                buffer,
-                buffer.length,
+            decoded = DYLIB.symbols.decode_p50k(
+                tokens,
+                tokens.length,
+                callback.pointer
+            )
+            break;
+        case 'r50k':
+            decoded = DYLIB.symbols.decode_r50k(
+                tokens,
+                tokens.length,
+                callback.pointer
+            )
+            break;
+        case 'cl100k':
+            decoded = DYLIB.symbols.decode_cl100k(
+                tokens,
+                tokens.length,
                 callback.pointer
             )
             break;
+
+        default:            
+            decoded = DYLIB.symbols.decode_p50k(
+                tokens,
+                tokens.length,
+                callback.pointer
+            )
+            break;
+    }
+    DYLIB.close();
+    if (!decoded) {
+        throw new RangeError("[ERROR]: Token id is not in the vocabulary.");
+    }
+    return Uint8Array.from(
+        pointer
+        // See https://developer.mozilla.org/en-US/docs/Web/JavaScript/Reference/Global_Objects/BigInt#comparisons for sorting bigint
+        .sort((a, b) => (a.idx < b.idx) ? -1 : ((a.idx > b.idx) ? 1 : 0))
+        .map((v, _index, _array) => v.value)
+    )
+};
+
+type Offset = {
+    token: number
+    start: number
+    end: number
+}
+
+// Offsets are UTF-16 code units, so `text.slice(start, end)` highlights the token in the JavaScript string.
+export function encodeWithOffsets (buffer: Uint8Array, vocabulary?: vocabulary): Array<Offset> {
+    const pointer: Array<{ idx: bigint } & Offset> = [];
+
+    const callback = new Deno.UnsafeCallback({
+        parameters: ["usize", width(vocabulary), "usize", "usize"],
+        result: "void"
+    }, function (idx: bigint, token: number, start: number | bigint, end: number | bigint): void {
+        pointer.push({idx, token, start: Number(start), end: Number(end)})
+    });
+
+    const DYLIB = Deno.dlopen(FOREIGN_INTERFACE, SYMBOLS);
+    switch (vocabulary) {
         case 'r50k':
-            DYLIB.symbols.decode_r50k(
+            DYLIB.symbols.encode_offsets_r50k(
                 buffer,
                 buffer.length,
+                true,
                 callback.pointer
             )
             break;
         case 'cl100k':
-            DYLIB.symbols.decode_cl100k(
+            DYLIB.symbols.encode_offsets_cl100k(
                 buffer,
                 buffer.length,
+                true,
                 callback.pointer
             )
             break;
 
-        default:            
-            DYLIB.symbols.decode_p50k(
+        default:
+            DYLIB.symbols.encode_offsets_p50k(
                 buffer,
                 buffer.length,
+                true,
                 callback.pointer
             )
             break;
     }
     DYLIB.close();
-    return Uint8Array.from(
-        pointer
+
+    return pointer
         // See https://developer.mozilla.org/en-US/docs/Web/JavaScript/Reference/Global_Objects/BigInt#comparisons for sorting bigint
         .sort((a, b) => (a.idx < b.idx) ? -1 : ((a.idx > b.idx) ? 1 : 0))
-        .map((v, _index, _array) => v.value)
-    )
+        .map(({token, start, end}) => ({token, start, end}))
 };
 
 import { assertEquals } from "jsr:@std/assert"
@@ -158,6 +270,11 @@ const decoding = new TextDecoder().decode(decode(encoding, 'r50k'));
 
 assertEquals(test, decoding)
 
+// cl100k ids above 65535 must survive both directions of the C ABI.
+assertEquals(encode(new TextEncoder().encode(" mouths"), 'cl100k'), Uint32Array.from([65609]))
+assertEquals(new TextDecoder().decode(decode(Uint32Array.from([65609]), 'cl100k')), " mouths")
+assertEquals(count(new TextEncoder().encode(test), 'r50k'), encoding.length)
+
 console.log(`Encode: '${test}' -> ${encoding}`);
 // console.log(`Decode: '${encoding}' -> ${decoding}`);
 // console.log(`indivisible values. -> ${decode(new Uint16Array([521, 452, 12843, 1988, 82]), 'r50k')}`);
@@ -0,0 +1,188 @@
+use super::vocabulary::Encoding;
+use serde_json::Value;
+
+/// A chat message.
+///
+/// ## Message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Message {
+    /// Author of the message: `system`, `user`, `assistant`...
+    pub role: String,
+
+    /// Text of the message, empty for messages without text.
+    pub content: String,
+
+    /// Optional name of the author.
+    pub name: Option<String>,
+}
+
+/// Tokens a model adds around messages, on top of the tokens of their fields.
+///
+/// ## Framing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Framing {
+    ///
+    /// ## Encoding
+    pub encoding: Encoding,
+
+    /// Tokens added to every message, such as `<|im_start|>`, `\n`, `<|im_end|>` and `\n` in ChatML.
+    pub per_message: i64,
+
+    /// Tokens added, or removed when negative, by the `name` of a message.
+    pub per_name: i64,
+
+    /// Tokens priming the reply of the model, such as `<|im_start|>assistant<|im_sep|>`.
+    pub reply: i64,
+
+    /// Tokens framing the [tool definitions](super::tools::render) of a prompt.
+    pub tools: i64,
+}
+
+impl Framing {
+    /// ChatML framing of `gpt-3.5-turbo-0301`: `<|im_start|>{role or name}\n{content}<|im_end|>\n`.
+    pub const CHATML: Framing = Framing { encoding: Encoding::CL100K, per_message: 4, per_name: -1, reply: 3, tools: 9 };
+
+    /// Framing of the later GPT-3.5 and GPT-4 models, three tokens per message and one per name.
+    pub const CL100K: Framing = Framing { encoding: Encoding::CL100K, per_message: 3, per_name: 1, reply: 3, tools: 9 };
+
+    /// Framing of the GPT-4o models, with the same constants over the `o200k` vocabulary.
+    pub const O200K: Framing = Framing { encoding: Encoding::O200K, per_message: 3, per_name: 1, reply: 3, tools: 9 };
+
+    /// Framing of a model, matched on its name or the prefix of its family.
+    ///
+    /// ## Model
+    /// ### Arguments
+    /// * `model` - a model name, e.g. `gpt-4-0613`.
+    ///
+    /// ### Returns
+    /// * the framing of the model, or an error for unknown models.
+    pub fn model(model: &str) -> Result<Framing, String> {
+        match model {
+            "gpt-3.5-turbo-0301" | "chatml" => Ok(Framing::CHATML),
+            _ if model.starts_with("gpt-4o") || model.starts_with("o1") || model.starts_with("o3") => Ok(Framing::O200K),
+            _ if model.starts_with("gpt-4") || model.starts_with("gpt-3.5-turbo") || model.starts_with("gpt-35-turbo") => {
+                Ok(Framing::CL100K)
+            }
+            _ => Err(format!("[ERROR]: Unknown model '{model}', expected a gpt-3.5-turbo, gpt-4, gpt-4o, o1 or o3 model.")),
+        }
+    }
+}
+
+/// Reads the text of a message content: a string, text parts, or nothing.
+fn content(value: Option<&Value>) -> Result<String, String> {
+    match value {
+        None | Some(Value::Null) => Ok(String::new()),
+        Some(Value::String(text)) => Ok(text.clone()),
+        Some(Value::Array(parts)) => Ok(parts.iter().filter_map(|part| part.get("text").and_then(Value::as_str)).collect()),
+        Some(_) => Err("[ERROR]: 'content' must be a string, an array of parts or null.".to_string()),
+    }
+}
+
+/// Reads chat messages from a JSON array of `{"role", "content", "name"}` objects.
+///
+/// `content` is a string, an array of parts whose `text` fields are joined, or null; `name` is optional.
+///
+/// ## Messages
+/// ### Arguments
+/// * `value` - the JSON array.
+///
+/// ### Returns
+/// * the messages, or an error message.
+pub fn messages(value: &Value) -> Result<Vec<Message>, String> {
+    let Some(messages) = value.as_array() else {
+        return Err("[ERROR]: Messages must be a JSON array.".to_string());
+    };
+    messages
+        .iter()
+        .enumerate()
+        .map(|(index, message)| {
+            let role = message
+                .get("role")
+                .and_then(Value::as_str)
+                .ok_or(format!("[ERROR]: Message {index} has no 'role' string."))?;
+            let name = match message.get("name") {
+                None | Some(Value::Null) => None,
+                Some(Value::String(name)) => Some(name.clone()),
+                Some(_) => return Err(format!("[ERROR]: Message {index} has a 'name' that is not a string.")),
+            };
+            let content = content(message.get("content")).map_err(|error| format!("{error} (message {index})"))?;
+            Ok(Message { role: role.to_string(), content, name })
+        })
+        .collect()
+}
+
+/// Counts the prompt tokens of a conversation, framing included.
+///
+/// Every message costs `per_message` tokens plus the tokens of its role, content and name,
+/// a name adding `per_name` more; the conversation then costs `reply` tokens priming the answer.
+/// The vocabulary file of the framing encoding must be [available](Encoding::available).
+///
+/// ## Count
+/// ### Arguments
+/// * `messages` - the conversation.
+/// * `framing` - the framing of the model.
+///
+/// ### Returns
+/// * number of prompt tokens.
+pub fn count(messages: &[Message], framing: &Framing) -> usize {
+    let tokens = |text: &str| framing.encoding.count(text.as_bytes()) as i64;
+    let total: i64 = messages
+        .iter()
+        .map(|message| {
+            let name = match &message.name {
+                Some(name) => tokens(name) + framing.per_name,
+                None => 0,
+            };
+            framing.per_message + tokens(&message.role) + tokens(&message.content) + name
+        })
+        .sum::<i64>()
+        + framing.reply;
+    usize::try_from(total).unwrap_or(0)
+}
+
+/// Counts the prompt tokens of a conversation with tool definitions.
+///
+/// Tool definitions join the system message when there is one, sharing 4 tokens of its framing.
+///
+/// ## Prompt
+/// ### Arguments
+/// * `messages` - the conversation.
+/// * `tools` - a JSON array of [tool definitions](super::tools::render).
+/// * `framing` - the framing of the model.
+///
+/// ### Returns
+/// * number of prompt tokens, or an error message.
+pub fn prompt(messages: &[Message], tools: &Value, framing: &Framing) -> Result<usize, String> {
+    let tools = super::tools::count(tools, framing)?;
+    let shared = match tools > 0 && messages.iter().any(|message| message.role == "system") {
+        true => 4,
+        false => 0,
+    };
+    Ok((count(messages, framing) + tools).saturating_sub(shared))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bpe::chat;
+
+    #[test]
+    fn count() {
+        let messages = chat::messages(&serde_json::json!([
+            { "role": "system", "content": "hello world" },
+            { "role": "user", "name": "bob", "content": [{ "type": "text", "text": "hello" }, { "type": "image_url" }] },
+            { "role": "assistant", "content": null },
+        ]))
+        .unwrap();
+        assert_eq!(messages[1], chat::Message { role: "user".to_string(), content: "hello".to_string(), name: Some("bob".to_string()) });
+
+        // Tokens of the fields: system 1, hello world 2, user 1, hello 1, bob 1, assistant 1.
+        assert_eq!(chat::Framing::model("gpt-4-0613"), Ok(chat::Framing::CL100K));
+        assert_eq!(chat::count(&messages, &chat::Framing::CL100K), 3 * 3 + 7 + 1 + 3);
+        assert_eq!(chat::count(&messages, &chat::Framing::CHATML), 3 * 4 + 7 - 1 + 3);
+        assert_eq!(chat::count(&[], &chat::Framing::CL100K), 3);
+
+        assert!(chat::Framing::model("llama").is_err());
+        assert!(chat::messages(&serde_json::json!([{ "content": "x" }])).is_err());
+        assert!(chat::messages(&serde_json::json!({ "role": "user" })).is_err());
+    }
+}
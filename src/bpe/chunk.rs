@@ -0,0 +1,391 @@
+use std::collections::BTreeMap;
+use std::ops::Range;
+use std::sync::LazyLock;
+
+/// A window of tokens over a text.
+///
+/// ## Chunk
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chunk<'a, D> {
+    /// Text of the chunk, always cut on character boundaries.
+    pub text: &'a str,
+
+    /// Tokens of the chunk, decoding to exactly the chunk text.
+    pub tokens: Vec<D>,
+
+    /// Byte range of the chunk in the original text.
+    pub range: Range<usize>,
+}
+
+/// Kind of boundary a chunk can end on, from the least to the most preferred.
+///
+/// ## Boundary
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Boundary {
+    Token,
+    Pretoken,
+    Sentence,
+    Paragraph,
+}
+
+/// Classifies the boundary at a byte offset of a text.
+///
+/// ## Boundary
+/// ### Arguments
+/// * `text` - a text.
+/// * `offset` - a byte offset on a character boundary.
+/// * `pretokens` - start offsets of the token contractions of the text.
+///
+/// ### Returns
+/// * the kind of boundary.
+fn boundary(text: &str, offset: usize, pretokens: &[usize]) -> Boundary {
+    let before = &text[..offset];
+    let content = before.trim_end();
+    let whitespace = &before[content.len()..];
+
+    if content.is_empty() || offset == text.len() || whitespace.matches('\n').count() >= 2 {
+        return Boundary::Paragraph;
+    };
+    let spaced = !whitespace.is_empty() || text[offset..].starts_with(char::is_whitespace);
+    if spaced && content.ends_with(['.', '!', '?']) {
+        return Boundary::Sentence;
+    };
+    match pretokens.binary_search(&offset) {
+        Ok(_) => Boundary::Pretoken,
+        Err(_) => Boundary::Token,
+    }
+}
+
+/// Splits a text into chunks of at most `size` tokens, consecutive chunks sharing about `overlap` tokens.
+///
+/// A chunk ends on the most preferred boundary (paragraph, sentence, then token contraction)
+/// found within its last `slack` tokens, and the latest one among equally preferred boundaries.
+/// Chunks never cut a multibyte character, so a chunk can be a few tokens shorter than `size`,
+/// and `size` must hold the tokens of the widest character of the text.
+///
+/// ## Chunk
+/// ### Arguments
+/// * `text` - a text.
+/// * `lookup` - a lookup table with vocabulary scheme (slice to tokens).
+/// * `size` - the maximum number of tokens per chunk.
+/// * `overlap` - the number of tokens repeated at the start of the next chunk, less than `size`.
+/// * `slack` - the number of tokens a chunk may give up to end on a better boundary.
+///
+/// ### Returns
+/// * the chunks, in order, or an error when `size` is zero, `overlap` is not less than `size`
+///   or a character of the text takes more than `size` tokens.
+pub fn chunk<'a, D: std::clone::Clone>(
+    text: &'a str,
+    lookup: &LazyLock<BTreeMap<Vec<u8>, D>>,
+    size: usize,
+    overlap: usize,
+    slack: usize,
+) -> Result<Vec<Chunk<'a, D>>, String> where usize: TryFrom<D> {
+    if size == 0 {
+        return Err("[ERROR]: Chunk size must be positive.".to_string());
+    };
+    if overlap >= size {
+        return Err("[ERROR]: Overlap must be smaller than the chunk size.".to_string());
+    };
+
+    let offsets = super::encode_with_offsets(text.as_bytes(), lookup);
+    let pretokens: Vec<usize> = super::spans(text.as_bytes(), super::Pattern::of(lookup)).map(|span| span.start).collect();
+    // Byte offset where the token at each index starts, and the end of the text.
+    let cut = |index: usize| -> usize {
+        match offsets.get(index) {
+            Some((_, start, _)) => *start,
+            None => text.len(),
+        }
+    };
+
+    // Tokens of the widest character, the smallest window that can still end on a character boundary.
+    let widest = (1..=offsets.len())
+        .filter(|index| text.is_char_boundary(cut(*index)))
+        .scan(0, |previous, index| Some(index - std::mem::replace(previous, index)))
+        .max()
+        .unwrap_or(0);
+    if widest > size {
+        return Err(format!("[ERROR]: Chunk size {size} is smaller than the {widest} tokens of a single character."));
+    };
+
+    let mut chunks = vec![];
+    let mut start = 0;
+    while start < offsets.len() {
+        let limit = (start + size).min(offsets.len());
+        let end = (limit.saturating_sub(slack).max(start + 1)..=limit)
+            .filter(|index| text.is_char_boundary(cut(*index)))
+            .max_by_key(|index| (boundary(text, cut(*index), &pretokens), *index))
+            .or_else(|| (start + 1..=limit).rev().find(|index| text.is_char_boundary(cut(*index))))
+            .unwrap_or(limit);
+
+        let range = cut(start)..cut(end);
+        chunks.push(Chunk {
+            text: &text[range.clone()],
+            tokens: offsets[start..end].iter().map(|(token, _, _)| token.clone()).collect(),
+            range,
+        });
+        if end == offsets.len() {
+            break;
+        };
+
+        start = end.saturating_sub(overlap).max(start + 1);
+        while !text.is_char_boundary(cut(start)) {
+            start += 1;
+        }
+    }
+    Ok(chunks)
+}
+
+/// Structure a [structured](structured) chunker looks for.
+///
+/// ## Format
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Headings, fenced code blocks and paragraphs.
+    Markdown,
+    /// Top-level items (functions, classes) found by indentation and braces, then blocks.
+    Code,
+}
+
+impl std::str::FromStr for Format {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format {
+            "markdown" | "md" => Ok(Format::Markdown),
+            "code" => Ok(Format::Code),
+            _ => Err(format!("[ERROR]: Unknown format '{format}', expected 'markdown' or 'code'.")),
+        }
+    }
+}
+
+/// Lines of a range of text, with their byte ranges in the text.
+///
+/// ## Lines
+fn lines(text: &str, range: Range<usize>) -> Vec<(Range<usize>, &str)> {
+    let mut cursor = range.start;
+    text[range]
+        .split_inclusive('\n')
+        .map(|line| -> (Range<usize>, &str) {
+            let start = cursor;
+            cursor += line.len();
+            (start..cursor, line)
+        })
+        .collect()
+}
+
+/// Whether a line opens or closes a Markdown fenced code block.
+///
+/// ## Fence
+fn is_fence(line: &str) -> bool {
+    let line = line.trim_start();
+    line.starts_with("```") || line.starts_with("~~~")
+}
+
+/// Depth of a Markdown heading line, `0` for other lines.
+///
+/// ## Heading
+fn heading(line: &str) -> usize {
+    let depth = line.chars().take_while(|c| *c == '#').count();
+    match depth > 0 && depth <= 6 && line[depth..].starts_with([' ', '\t', '\n']) {
+        true => depth,
+        false => 0,
+    }
+}
+
+/// Byte offsets where the structural units of a range of text start, at a given level.
+///
+/// Markdown levels split before headings of depth one to six, then into blocks
+/// (paragraphs and whole fenced code blocks), then into lines.
+/// Code levels split before top-level items, then into blocks separated by blank lines, then into lines.
+///
+/// ## Boundaries
+/// ### Arguments
+/// * `text` - a text.
+/// * `range` - the byte range to split.
+/// * `format` - the structure to look for.
+/// * `level` - the structural level.
+///
+/// ### Returns
+/// * unit start offsets, or `None` when there are no more levels.
+fn boundaries(text: &str, range: Range<usize>, format: Format, level: usize) -> Option<Vec<usize>> {
+    let lines = lines(text, range);
+    let mut starts = vec![];
+    match (format, level) {
+        (Format::Markdown, 0..=5) => {
+            let mut fenced = false;
+            for (span, line) in &lines {
+                if is_fence(line) {
+                    fenced = !fenced;
+                } else if !fenced && (1..=level + 1).contains(&heading(line)) {
+                    starts.push(span.start);
+                };
+            }
+        }
+        (Format::Markdown, 6) => {
+            let mut fenced = false;
+            let mut blank = false;
+            for (span, line) in &lines {
+                if is_fence(line) {
+                    if !fenced {
+                        starts.push(span.start);
+                    };
+                    fenced = !fenced;
+                    blank = !fenced;
+                    continue;
+                };
+                if !fenced && !line.trim().is_empty() && blank {
+                    starts.push(span.start);
+                };
+                blank = !fenced && line.trim().is_empty();
+            }
+        }
+        (Format::Code, 0) => {
+            let mut depth: isize = 0;
+            let mut attached = false;
+            for (span, line) in &lines {
+                let trimmed = line.trim_start();
+                let top = depth <= 0 && !trimmed.is_empty() && trimmed.len() == line.len();
+                if top && !attached && !trimmed.starts_with(['}', ')', ']']) {
+                    starts.push(span.start);
+                };
+                if top || trimmed.is_empty() {
+                    attached = top && ["//", "#", "/*", "*", "@"].iter().any(|prefix| trimmed.starts_with(prefix));
+                };
+                for c in line.chars() {
+                    match c {
+                        '{' | '(' | '[' => depth += 1,
+                        '}' | ')' | ']' => depth -= 1,
+                        _ => {}
+                    };
+                }
+            }
+        }
+        (Format::Code, 1) => {
+            let mut blank = false;
+            for (span, line) in &lines {
+                if line.trim().is_empty() {
+                    blank = true;
+                } else if blank {
+                    starts.push(span.start);
+                    blank = false;
+                };
+            }
+        }
+        (Format::Markdown, 7) | (Format::Code, 2) => {
+            starts.extend(lines.iter().map(|(span, _)| span.start));
+        }
+        _ => return None,
+    };
+    Some(starts)
+}
+
+/// Splits a text into chunks of at most `size` tokens along its structure.
+///
+/// Structural units (sections, code blocks, paragraphs, top-level functions and classes) are kept whole
+/// and packed together while they fit the token budget.
+/// A unit that does not fit is split recursively at the next structural level,
+/// down to lines and finally [token windows](chunk).
+/// Chunk tokens are the encoding of the chunk text on its own.
+///
+/// ## Structured
+/// ### Arguments
+/// * `text` - a text.
+/// * `lookup` - a lookup table with vocabulary scheme (slice to tokens).
+/// * `size` - the maximum number of tokens per chunk.
+/// * `format` - the [structure](Format) to look for.
+///
+/// ### Returns
+/// * the chunks, in order, covering the whole text, or an error when `size` is zero
+///   or a character of the text takes more than `size` tokens.
+pub fn structured<'a, D: std::clone::Clone>(
+    text: &'a str,
+    lookup: &LazyLock<BTreeMap<Vec<u8>, D>>,
+    size: usize,
+    format: Format,
+) -> Result<Vec<Chunk<'a, D>>, String> where usize: TryFrom<D> {
+    if size == 0 {
+        return Err("[ERROR]: Chunk size must be positive.".to_string());
+    };
+    let mut chunks = vec![];
+    split(text, 0..text.len(), lookup, size, format, 0, &mut chunks)?;
+    Ok(chunks)
+}
+
+/// Splits a range of text at the first structural level that divides it, and packs the units into chunks.
+///
+/// ## Split
+fn split<'a, D: std::clone::Clone>(
+    text: &'a str,
+    range: Range<usize>,
+    lookup: &LazyLock<BTreeMap<Vec<u8>, D>>,
+    size: usize,
+    format: Format,
+    level: usize,
+    chunks: &mut Vec<Chunk<'a, D>>,
+) -> Result<(), String> where usize: TryFrom<D> {
+    let mut level = level;
+    while let Some(mut starts) = boundaries(text, range.clone(), format, level) {
+        starts.retain(|start| *start > range.start);
+        if !starts.is_empty() {
+            starts.insert(0, range.start);
+            starts.push(range.end);
+            let units = starts.windows(2).map(|pair| pair[0]..pair[1]);
+            return pack(text, units, lookup, size, format, level + 1, chunks);
+        };
+        level += 1;
+    }
+
+    for window in chunk(&text[range.clone()], lookup, size, 0, 0)? {
+        chunks.push(Chunk {
+            text: window.text,
+            tokens: window.tokens,
+            range: range.start + window.range.start..range.start + window.range.end,
+        });
+    }
+    Ok(())
+}
+
+/// Packs consecutive units into chunks while they fit the token budget, splitting the units that do not.
+///
+/// ## Pack
+fn pack<'a, D: std::clone::Clone>(
+    text: &'a str,
+    units: impl Iterator<Item = Range<usize>>,
+    lookup: &LazyLock<BTreeMap<Vec<u8>, D>>,
+    size: usize,
+    format: Format,
+    level: usize,
+    chunks: &mut Vec<Chunk<'a, D>>,
+) -> Result<(), String> where usize: TryFrom<D> {
+    let fits = |range: &Range<usize>| -> bool {
+        super::count_tokens_up_to(text[range.clone()].as_bytes(), lookup, size) != super::Count::Exceeded
+    };
+    let emit = |range: Range<usize>, chunks: &mut Vec<Chunk<'a, D>>| {
+        chunks.push(Chunk {
+            text: &text[range.clone()],
+            tokens: super::encode(text[range.clone()].as_bytes(), lookup),
+            range,
+        });
+    };
+
+    let mut current: Option<Range<usize>> = None;
+    for unit in units {
+        if let Some(packed) = current.take() {
+            let candidate = packed.start..unit.end;
+            if fits(&candidate) {
+                current = Some(candidate);
+                continue;
+            };
+            emit(packed, chunks);
+        };
+        match fits(&unit) {
+            true => current = Some(unit),
+            false => split(text, unit, lookup, size, format, level, chunks)?,
+        };
+    }
+    if let Some(packed) = current {
+        emit(packed, chunks);
+    };
+    Ok(())
+}
@@ -0,0 +1,79 @@
+use super::vocabulary::Encoding;
+
+/// Token statistics of a corpus under one encoding.
+///
+/// ## Statistics
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Statistics {
+    ///
+    /// ## Encoding
+    pub encoding: Encoding,
+
+    /// Number of documents in the corpus.
+    pub documents: usize,
+
+    /// Number of bytes in the corpus.
+    pub bytes: usize,
+
+    /// Number of tokens in the corpus.
+    pub tokens: usize,
+}
+
+impl Statistics {
+    /// Average number of bytes per token, higher is a more compact encoding.
+    ///
+    /// ## Bytes per token
+    pub fn bytes_per_token(&self) -> f64 {
+        match self.tokens {
+            0 => 0.0,
+            tokens => self.bytes as f64 / tokens as f64,
+        }
+    }
+}
+
+/// Compares the token counts of a corpus across encodings.
+///
+/// Every document is encoded on its own, so token contractions never span documents.
+/// The vocabulary files of the encodings must be [available](Encoding::available).
+///
+/// ## Compare
+/// ### Arguments
+/// * `corpus` - the documents.
+/// * `encodings` - the encodings to compare.
+///
+/// ### Returns
+/// * the statistics of every encoding, in the order of `encodings`.
+pub fn compare(corpus: &[&[u8]], encodings: &[Encoding]) -> Vec<Statistics> {
+    let bytes = corpus.iter().map(|document| document.len()).sum();
+    encodings
+        .iter()
+        .map(|encoding| Statistics {
+            encoding: *encoding,
+            documents: corpus.len(),
+            bytes,
+            tokens: corpus.iter().map(|document| encoding.count(document)).sum(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bpe::vocabulary::Encoding;
+
+    #[test]
+    fn compare() {
+        let corpus: [&[u8]; 2] = [b"Hello indivisible world!", b"    def tokenize(self):\n"];
+        let statistics = crate::bpe::compare::compare(&corpus, &[Encoding::R50K, Encoding::CL100K]);
+        assert_eq!(statistics.len(), 2);
+        assert_eq!(statistics[0].encoding, Encoding::R50K);
+        assert_eq!(statistics[0].documents, 2);
+        assert_eq!(statistics[0].bytes, 48);
+        assert_eq!(
+            statistics[0].tokens,
+            corpus.iter().map(|document| crate::bpe::count_tokens(document, &crate::bpe::vocabulary::R50K_TOKENS)).sum::<usize>()
+        );
+        assert!(statistics[1].tokens < statistics[0].tokens);
+        assert_eq!(statistics[1].bytes_per_token(), 48.0 / statistics[1].tokens as f64);
+        assert_eq!(crate::bpe::compare::compare(&[], &[Encoding::R50K])[0].bytes_per_token(), 0.0);
+    }
+}
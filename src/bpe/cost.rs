@@ -0,0 +1,108 @@
+use super::vocabulary::Encoding;
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// Prices of a model, per million tokens.
+///
+/// ## Price
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Price {
+    /// Price of a million prompt tokens.
+    pub input: f64,
+
+    /// Price of a million completion tokens.
+    pub output: f64,
+
+    /// Encoding of the model, the one of its [framing](super::chat::Framing::model) when missing.
+    pub encoding: Option<Encoding>,
+}
+
+impl Price {
+    /// Cost of a request.
+    ///
+    /// ## Cost
+    /// ### Arguments
+    /// * `input` - number of prompt tokens.
+    /// * `output` - number of completion tokens.
+    ///
+    /// ### Returns
+    /// * the cost, in the currency of the table.
+    pub fn cost(&self, input: usize, output: usize) -> f64 {
+        (input as f64 * self.input + output as f64 * self.output) / 1_000_000.0
+    }
+}
+
+/// Reads a price from the fields of a table entry.
+fn price(model: &str, fields: &serde_json::Map<String, Value>) -> Result<Price, String> {
+    let number = |field: &str| -> Result<f64, String> {
+        fields
+            .get(field)
+            .and_then(Value::as_f64)
+            .filter(|price| *price >= 0.0)
+            .ok_or(format!("[ERROR]: '{model}' must have a non-negative '{field}' price."))
+    };
+    let encoding = match fields.get("encoding") {
+        None | Some(Value::Null) => None,
+        Some(Value::String(encoding)) => Some(encoding.parse()?),
+        Some(_) => return Err(format!("[ERROR]: 'encoding' of '{model}' must be a string.")),
+    };
+    Ok(Price { input: number("input")?, output: number("output")?, encoding })
+}
+
+/// Reads a JSON price table: `{"model": {"input": 2.5, "output": 10, "encoding": "o200k"}}`, `encoding` being optional.
+///
+/// ## JSON
+/// ### Arguments
+/// * `text` - the table.
+///
+/// ### Returns
+/// * the price of every model, or an error message.
+pub fn json(text: &str) -> Result<BTreeMap<String, Price>, String> {
+    let table: Value = serde_json::from_str(text).map_err(|error| format!("[ERROR]: Invalid JSON: {error}"))?;
+    let Some(table) = table.as_object() else {
+        return Err("[ERROR]: The price table must be a JSON object.".to_string());
+    };
+    table
+        .iter()
+        .map(|(model, fields)| match fields.as_object() {
+            Some(fields) => Ok((model.clone(), price(model, fields)?)),
+            None => Err(format!("[ERROR]: '{model}' must be an object.")),
+        })
+        .collect()
+}
+
+/// Reads a JSON price table from a file.
+///
+/// ## Table
+/// ### Arguments
+/// * `path` - path of the table.
+///
+/// ### Returns
+/// * the price of every model, or an error message.
+pub fn table(path: &std::path::Path) -> Result<BTreeMap<String, Price>, String> {
+    let text = std::fs::read_to_string(path).map_err(|error| format!("[ERROR]: {}: {error}", path.display()))?;
+    json(&text)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bpe::cost;
+    use crate::bpe::vocabulary::Encoding;
+
+    #[test]
+    fn json() {
+        let table = cost::json(r#"{"gpt-3.5-turbo": {"input": 0.5, "output": 1500}, "local": {"input": 0, "output": 0, "encoding": "r50k"}}"#).unwrap();
+        assert_eq!(table.keys().collect::<Vec<&String>>(), vec!["gpt-3.5-turbo", "local"]);
+        assert_eq!(table["gpt-3.5-turbo"], cost::Price { input: 0.5, output: 1500.0, encoding: None });
+        assert_eq!(table["local"].encoding, Some(Encoding::R50K));
+        assert_eq!(table["gpt-3.5-turbo"].cost(2_000_000, 1_000), 2.5);
+
+        assert!(cost::json("[\"gpt-4o\"]\ninput = 2.5\noutput = 10").unwrap_err().starts_with("[ERROR]: Invalid JSON"));
+        assert!(cost::json(r#"[{"input": 1, "output": 1}]"#).is_err());
+        assert!(cost::json(r#"{"model": 1}"#).is_err());
+        assert!(cost::json(r#"{"model": {"input": 1}}"#).is_err());
+        assert!(cost::json(r#"{"model": {"input": -1, "output": 1}}"#).is_err());
+        assert!(cost::json(r#"{"model": {"input": 1, "output": 1, "encoding": 1}}"#).is_err());
+        assert!(cost::json(r#"{"model": {"input": 1, "output": 1, "encoding": "cl200k"}}"#).is_err());
+    }
+}
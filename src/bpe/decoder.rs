@@ -0,0 +1,79 @@
+use std::collections::BTreeMap;
+use std::sync::LazyLock;
+
+/// Incremental decoder for tokens arriving one at a time, for example while a model streams them.
+///
+/// A single token can end in the middle of a multibyte character.
+/// Those bytes are held back until the tokens completing the character arrive,
+/// so only complete UTF-8 text is emitted.
+pub struct StreamDecoder<'a, T> {
+    ///
+    /// ## Vocabulary
+    vocabulary: &'a LazyLock<BTreeMap<T, Vec<u8>>>,
+
+    /// Decoded bytes of an incomplete UTF-8 sequence.
+    ///
+    /// ## Pending
+    pending: Vec<u8>,
+}
+
+impl<'a, T: std::cmp::Ord + std::clone::Clone> StreamDecoder<'a, T> {
+    pub fn new(vocabulary: &'a LazyLock<BTreeMap<T, Vec<u8>>>) -> StreamDecoder<'a, T> {
+        StreamDecoder {
+            vocabulary,
+            pending: vec![],
+        }
+    }
+
+    /// Decodes a token and returns the text it completes.
+    ///
+    /// Bytes that can never become valid UTF-8 are emitted as `U+FFFD`,
+    /// an incomplete sequence at the end is held back for the next token.
+    ///
+    /// ## Push
+    /// ### Arguments
+    /// * `token` - the next token.
+    ///
+    /// ### Returns
+    /// * complete text, possibly empty, or the token when it is not in the vocabulary.
+    pub fn push(&mut self, token: &T) -> Result<String, T> {
+        self.pending.extend(super::decode(std::slice::from_ref(token), self.vocabulary)?);
+
+        let mut text = String::new();
+        let mut cursor = 0;
+        loop {
+            match std::str::from_utf8(&self.pending[cursor..]) {
+                Ok(valid) => {
+                    text.push_str(valid);
+                    cursor = self.pending.len();
+                    break;
+                }
+                Err(error) => {
+                    let valid = cursor + error.valid_up_to();
+                    text.push_str(std::str::from_utf8(&self.pending[cursor..valid]).unwrap());
+                    cursor = valid;
+                    match error.error_len() {
+                        Some(length) => {
+                            text.push(char::REPLACEMENT_CHARACTER);
+                            cursor += length;
+                        }
+                        None => break,
+                    };
+                }
+            };
+        }
+        self.pending.drain(..cursor);
+        Ok(text)
+    }
+
+    /// Ends the stream, returning any held back bytes as `U+FFFD`.
+    ///
+    /// ## Finish
+    /// ### Returns
+    /// * remaining text, empty when the stream ended on a complete character.
+    pub fn finish(&mut self) -> String {
+        let text = String::from_utf8_lossy(&self.pending).to_string();
+        self.pending.clear();
+        text
+    }
+}
@@ -0,0 +1,240 @@
+use std::collections::BTreeMap;
+use std::collections::VecDeque;
+use std::io::Read;
+use std::sync::LazyLock;
+
+/// Number of bytes read from a reader at a time.
+///
+/// ## Chunk
+const CHUNK: usize = 8 * 1024;
+
+/// Number of bytes past the end of a token contraction the [token regular expression](super::TOKENS_RE) can look at.
+/// That is one full UTF-8 character, which also covers the longest contraction (`'l l`).
+///
+/// ## Lookahead
+const LOOKAHEAD: usize = 4;
+
+/// Byte pair encoder bound to a vocabulary.
+pub struct Encoder<'a, D> {
+    ///
+    /// ## Vocabulary
+    vocabulary: &'a LazyLock<BTreeMap<Vec<u8>, D>>,
+}
+
+impl<'a, D: std::clone::Clone> Encoder<'a, D> where usize: TryFrom<D> {
+    pub fn new(vocabulary: &'a LazyLock<BTreeMap<Vec<u8>, D>>) -> Encoder<'a, D> {
+        Encoder { vocabulary }
+    }
+
+    /// Encodes a given byte slice into a token vector.
+    /// See [encode](super::encode) for implementation.
+    ///
+    /// ## Encode
+    /// ### Arguments
+    /// * `slice` - a byte vector.
+    ///
+    /// ### Returns
+    /// * a token vector equivalent of slice.
+    pub fn encode(&self, slice: &[u8]) -> Vec<D> {
+        super::encode(slice, self.vocabulary)
+    }
+
+    /// Counts the tokens a given byte slice encodes to.
+    /// See [count tokens](super::count_tokens) for implementation.
+    ///
+    /// ## Count tokens
+    /// ### Arguments
+    /// * `slice` - a byte vector.
+    ///
+    /// ### Returns
+    /// * number of tokens.
+    pub fn count_tokens(&self, slice: &[u8]) -> usize {
+        super::count_tokens(slice, self.vocabulary)
+    }
+
+    /// Counts the tokens a given byte slice encodes to, up to a limit.
+    /// See [count tokens up to](super::count_tokens_up_to) for implementation.
+    ///
+    /// ## Count tokens up to
+    /// ### Arguments
+    /// * `slice` - a byte vector.
+    /// * `limit` - the maximum number of tokens.
+    ///
+    /// ### Returns
+    /// * the exact count, or exceeded.
+    pub fn count_tokens_up_to(&self, slice: &[u8], limit: usize) -> super::Count {
+        super::count_tokens_up_to(slice, self.vocabulary, limit)
+    }
+
+    /// Encodes a given byte slice, recording the merges of every token contraction.
+    /// See [explain](super::explain) for implementation.
+    ///
+    /// ## Explain
+    /// ### Arguments
+    /// * `slice` - a byte vector.
+    ///
+    /// ### Returns
+    /// * a trace per token contraction.
+    pub fn explain(&self, slice: &[u8]) -> Vec<super::Trace<D>> {
+        super::explain(slice, self.vocabulary)
+    }
+
+    /// Truncates a text to at most `max_tokens` tokens.
+    /// See [truncate](super::truncate) for implementation.
+    ///
+    /// ## Truncate
+    /// ### Arguments
+    /// * `text` - a text.
+    /// * `max_tokens` - the maximum number of tokens to keep.
+    /// * `side` - the side tokens are cut from.
+    ///
+    /// ### Returns
+    /// * the truncated text and the kept tokens.
+    pub fn truncate<'t>(&self, text: &'t str, max_tokens: usize, side: super::Side) -> (&'t str, Vec<D>) {
+        super::truncate(text, self.vocabulary, max_tokens, side)
+    }
+
+    /// Splits a text into overlapping chunks of at most `size` tokens.
+    /// See [chunk](super::chunk::chunk) for implementation.
+    ///
+    /// ## Chunk
+    /// ### Arguments
+    /// * `text` - a text.
+    /// * `size` - the maximum number of tokens per chunk.
+    /// * `overlap` - the number of tokens repeated at the start of the next chunk.
+    /// * `slack` - the number of tokens a chunk may give up to end on a better boundary.
+    ///
+    /// ### Returns
+    /// * the chunks, in order, or an error for sizes [chunk](super::chunk::chunk) rejects.
+    pub fn chunk<'t>(&self, text: &'t str, size: usize, overlap: usize, slack: usize) -> Result<Vec<super::chunk::Chunk<'t, D>>, String> {
+        super::chunk::chunk(text, self.vocabulary, size, overlap, slack)
+    }
+
+    /// Splits a text into chunks of at most `size` tokens along its structure.
+    /// See [structured](super::chunk::structured) for implementation.
+    ///
+    /// ## Structured
+    /// ### Arguments
+    /// * `text` - a text.
+    /// * `size` - the maximum number of tokens per chunk.
+    /// * `format` - the structure to look for.
+    ///
+    /// ### Returns
+    /// * the chunks, in order.
+    pub fn structured<'t>(&self, text: &'t str, size: usize, format: super::chunk::Format) -> Result<Vec<super::chunk::Chunk<'t, D>>, String> {
+        super::chunk::structured(text, self.vocabulary, size, format)
+    }
+
+    /// Encodes everything read from a reader, yielding tokens as soon as they are final.
+    ///
+    /// Only the bytes after the last safe token contraction boundary are buffered,
+    /// so memory stays bounded by the longest contraction rather than the input.
+    /// The tokens are identical to [encoding](super::encode) the whole input at once.
+    ///
+    /// ## Encode reader
+    /// ### Arguments
+    /// * `reader` - a byte reader.
+    ///
+    /// ### Returns
+    /// * an iterator of tokens, or the read error.
+    pub fn encode_reader<R: Read>(&self, reader: R) -> EncodeReader<'a, R, D> {
+        EncodeReader {
+            vocabulary: self.vocabulary,
+            reader,
+            buffer: vec![],
+            unicodes: vec![],
+            tokens: VecDeque::new(),
+            eof: false,
+        }
+    }
+}
+
+/// Streaming encoder over a [reader](std::io::Read).
+/// See [encode reader](Encoder::encode_reader).
+pub struct EncodeReader<'a, R, D> {
+    ///
+    /// ## Vocabulary
+    vocabulary: &'a LazyLock<BTreeMap<Vec<u8>, D>>,
+
+    ///
+    /// ## Reader
+    reader: R,
+
+    /// Bytes read but not encoded yet.
+    ///
+    /// ## Buffer
+    buffer: Vec<u8>,
+
+    /// Scratch buffer for vocabulary lookups.
+    ///
+    /// ## Unicodes
+    unicodes: Vec<u8>,
+
+    /// Tokens encoded but not yielded yet.
+    ///
+    /// ## Tokens
+    tokens: VecDeque<D>,
+
+    /// Whether the reader is exhausted.
+    ///
+    /// ## End of file
+    eof: bool,
+}
+
+impl<R: Read, D: std::clone::Clone> EncodeReader<'_, R, D> where usize: TryFrom<D> {
+    /// Encodes the buffered token contractions that can no longer change.
+    ///
+    /// A contraction is final once a full character past its end has been read, or the reader is exhausted.
+    ///
+    /// ## Flush
+    fn flush(&mut self) {
+        let spans: Vec<std::ops::Range<usize>> = super::spans(&self.buffer, super::Pattern::of(self.vocabulary)).collect();
+        let boundary = match self.eof {
+            true => self.buffer.len(),
+            false => spans
+                .iter()
+                .rev()
+                .map(|span| span.end)
+                .find(|end| end + LOOKAHEAD <= self.buffer.len())
+                .unwrap_or(0),
+        };
+
+        for span in spans.into_iter().take_while(|span| span.end <= boundary) {
+            self.tokens.extend(super::lexeme(&self.buffer[span], self.vocabulary, &mut self.unicodes));
+        }
+        self.buffer.drain(..boundary);
+    }
+}
+
+impl<R: Read, D: std::clone::Clone> Iterator for EncodeReader<'_, R, D> where usize: TryFrom<D> {
+    type Item = std::io::Result<D>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(token) = self.tokens.pop_front() {
+                return Some(Ok(token));
+            };
+            if self.eof {
+                return None;
+            };
+
+            let length = self.buffer.len();
+            self.buffer.resize(length + CHUNK, 0);
+            match self.reader.read(&mut self.buffer[length..]) {
+                Ok(read) => {
+                    self.buffer.truncate(length + read);
+                    self.eof = read == 0;
+                }
+                Err(error) if error.kind() == std::io::ErrorKind::Interrupted => {
+                    self.buffer.truncate(length);
+                    continue;
+                }
+                Err(error) => {
+                    self.buffer.truncate(length);
+                    return Some(Err(error));
+                }
+            };
+            self.flush();
+        }
+    }
+}
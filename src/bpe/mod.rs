@@ -1,36 +1,204 @@
+//! Module inspired by [PicoGPT](https://github.com/jaymody/picoGPT) project.
 mod unit;
-pub(crate) mod vocabulary;
+pub mod chat;
+pub mod chunk;
+pub mod compare;
+pub mod cost;
+pub mod decoder;
+pub mod encoder;
+pub mod tools;
+pub mod tree;
+pub mod vocabulary;
 
-use std::mem::swap;
 use regex::bytes::Regex;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::ops::Range;
 use std::sync::LazyLock;
 use std::collections::BTreeMap;
-use std::collections::HashMap;
-use std::collections::HashSet;
 use unicode_segmentation::UnicodeSegmentation;
-///! Module inspired by [PicoGPT](https://github.com/jaymody/picoGPT) project.
+
+/// Merge priority of a byte pair, taken from the vocabulary token it produces.
+/// Lower ranks are merged first.
+///
+/// ## Rank
+type Rank = usize;
+
+/// Symbol boundary inside a single byte buffer, linked to the boundaries before and after it,
+/// with the [rank](Rank) of merging the symbol starting there with the symbol that follows it.
 ///
+/// ## Part
+#[derive(Debug, Clone, Copy)]
+struct Part {
+    /// Byte position of the previous boundary.
+    previous: usize,
+
+    /// Byte position of the next boundary.
+    next: usize,
 
-/// Data structure for byte pairings of type `[T]`.
+    /// Rank of merging with the next symbol, `Rank::MAX` once merged away.
+    rank: Rank,
+}
+
+/// Token count against an upper bound.
 ///
-/// ## Byte Pair
-type BytePair<Type> = [Vec<Type>; 2];
+/// ## Count
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Count {
+    /// The exact number of tokens, at most the limit.
+    Exact(usize),
+    /// There are more tokens than the limit.
+    Exceeded,
+}
+
+/// Side of a text tokens are cut from when truncating.
+///
+/// ## Side
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    /// Cut tokens from the start, keeping the end of the text.
+    Start,
+    /// Cut tokens from the end, keeping the start of the text.
+    End,
+}
+
+impl std::str::FromStr for Side {
+    type Err = String;
+
+    fn from_str(side: &str) -> Result<Self, Self::Err> {
+        match side {
+            "start" => Ok(Side::Start),
+            "end" => Ok(Side::End),
+            _ => Err(format!("[ERROR]: Unknown side '{side}', expected 'start' or 'end'.")),
+        }
+    }
+}
 
-/// Data structure for mapping byte pairings to tokens of type `[T]`.
+/// A merge applied while encoding a token contraction.
 ///
-/// ## Token pairing
-type TokenPairing<Type> = (usize, BytePair<Type>);
-/// Data structure for storing a text grapheme of type `[T]`.
+/// ## Merge
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Merge<D> {
+    /// Rank of the merge, the vocabulary value of the merged symbol.
+    pub rank: usize,
+
+    /// Byte ranges of the two merged symbols in the slice.
+    pub pair: (Range<usize>, Range<usize>),
+
+    /// Symbols of the contraction once the merge is applied, with their byte ranges in the slice.
+    pub symbols: Vec<(D, Range<usize>)>,
+}
+
+/// How a token contraction was encoded.
 ///
-/// ## Grapheme
-// type Grapheme64<Type> = Vec<[Type; 64]>;
-type Grapheme<Type> = Vec<Vec<Type>>;
+/// ## Trace
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Trace<D> {
+    /// Byte range of the contraction in the slice.
+    pub range: Range<usize>,
+
+    /// Whether the whole contraction is a vocabulary token, looked up without merging.
+    pub direct: bool,
+
+    /// Single byte symbols the merges start from, with their byte ranges in the slice.
+    pub bytes: Vec<(D, Range<usize>)>,
+
+    /// Merges in the order they were applied, also run for a [direct](Trace::direct) lookup to show how its token is built.
+    pub merges: Vec<Merge<D>>,
+
+    /// Tokens of the contraction.
+    pub tokens: Vec<D>,
+}
 
 /// Regular expression pattern for finding token contractions.
 ///
+/// The original ends with `\s+(?!\S)|\s+`, a lookahead the regex crate does not support:
+/// [spans](crate::bpe::spans) gives the last whitespace of a run back to the contraction that follows instead.
+///
 /// ## Tokens regular expression
 const TOKENS_RE: &str =
-    r"(u)'s|'t|'re|'ve|'m|'l l|'d| ?\p{L}+| ?\p{N}+| ?[^\s\p{L}\p{N}]+|\s+(\S)|\s+";
+    r"'s|'t|'re|'ve|'m|'ll|'d| ?\p{L}+| ?\p{N}+| ?[^\s\p{L}\p{N}]+|\s+";
+
+/// Compiled [token regular expression](crate::bpe::TOKENS_RE).
+///
+/// ## Tokenizer
+static TOKENIZER: LazyLock<Regex> = LazyLock::new(|| Regex::new(TOKENS_RE).unwrap());
+
+/// Regular expression pattern of the `cl100k` pretokenizer, with the same lookahead workaround as [GPT-2](crate::bpe::TOKENS_RE).
+///
+/// ## CL100K regular expression
+const CL100K_RE: &str =
+    r"(?i:'s|'t|'re|'ve|'m|'ll|'d)|[^\r\n\p{L}\p{N}]?\p{L}+|\p{N}{1,3}| ?[^\s\p{L}\p{N}]+[\r\n]*|\s*[\r\n]+|\s+";
+
+/// Compiled [cl100k regular expression](crate::bpe::CL100K_RE).
+///
+/// ## CL100K tokenizer
+static CL100K_TOKENIZER: LazyLock<Regex> = LazyLock::new(|| Regex::new(CL100K_RE).unwrap());
+
+/// Regular expression pattern of the `o200k` pretokenizer, with the same lookahead workaround as [cl100k](crate::bpe::CL100K_RE).
+///
+/// ## O200K regular expression
+const O200K_RE: &str = concat!(
+    r"[^\r\n\p{L}\p{N}]?[\p{Lu}\p{Lt}\p{Lm}\p{Lo}\p{M}]*[\p{Ll}\p{Lm}\p{Lo}\p{M}]+(?i:'s|'t|'re|'ve|'m|'ll|'d)?",
+    r"|[^\r\n\p{L}\p{N}]?[\p{Lu}\p{Lt}\p{Lm}\p{Lo}\p{M}]+[\p{Ll}\p{Lm}\p{Lo}\p{M}]*(?i:'s|'t|'re|'ve|'m|'ll|'d)?",
+    r"|\p{N}{1,3}| ?[^\s\p{L}\p{N}]+[\r\n/]*|\s*[\r\n]+|\s+",
+);
+
+/// Compiled [o200k regular expression](crate::bpe::O200K_RE).
+///
+/// ## O200K tokenizer
+static O200K_TOKENIZER: LazyLock<Regex> = LazyLock::new(|| Regex::new(O200K_RE).unwrap());
+
+/// Pretokenizer splitting text into token contractions before merging, which differs between encodings.
+///
+/// ## Pattern
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pattern {
+    /// The [GPT-2 expression](crate::bpe::TOKENS_RE) of `r50k` and `p50k`.
+    Gpt2,
+    /// The [cl100k expression](crate::bpe::CL100K_RE), with case-insensitive contractions and numbers of up to three digits.
+    Cl100k,
+    /// The [o200k expression](crate::bpe::O200K_RE), also splitting words on case changes.
+    O200k,
+}
+
+impl Pattern {
+    /// Pattern of a lookup table: the one of the [encoding](vocabulary::Encoding::of) it belongs to, GPT-2 otherwise.
+    ///
+    /// ## Of
+    pub fn of<D>(lookup: &LazyLock<BTreeMap<Vec<u8>, D>>) -> Pattern {
+        match vocabulary::Encoding::of(lookup) {
+            Some(encoding) => encoding.pattern(),
+            None => Pattern::Gpt2,
+        }
+    }
+
+    /// Finds the first token contraction starting at or after `start`.
+    ///
+    /// ## Find at
+    /// ### Returns
+    /// * the byte range of the contraction.
+    fn find_at(&self, slice: &[u8], start: usize) -> Option<Range<usize>> {
+        let regex = match self {
+            Pattern::Gpt2 => &TOKENIZER,
+            Pattern::Cl100k => &CL100K_TOKENIZER,
+            Pattern::O200k => &O200K_TOKENIZER,
+        };
+        let range = regex.find_at(slice, start)?.range();
+        // Emulates `\s+(?!\S)`: a whitespace run followed by more text leaves its last character to the next contraction.
+        // In cl100k and o200k, runs ending in a line break come from `\s*[\r\n]+` and are kept whole.
+        let text = std::str::from_utf8(&slice[range.clone()]).unwrap_or_default();
+        let trailing = match text.chars().next_back() {
+            Some('\r' | '\n') if *self != Pattern::Gpt2 => 0,
+            Some(last) if text.chars().all(char::is_whitespace) => last.len_utf8(),
+            _ => 0,
+        };
+        match range.end < slice.len() && text.len() > trailing && trailing > 0 {
+            true => Some(range.start..range.end - trailing),
+            false => Some(range),
+        }
+    }
+}
 
 /// I like the original comment on this. So I'm keeping it.
 ///
@@ -95,296 +263,325 @@ static UNICODES_TO_BYTES: LazyLock<BTreeMap<Vec<u8>, u8>> = LazyLock::new(|| {
     unicodes
 });
 
-/// ## Merges
-static MERGES: LazyLock<HashMap<Vec<u8>, usize>> = LazyLock::new(|| {
-    let mut encoder = HashMap::new();
-    let file = std::fs::File::open("src/bpe/merges.txt")
-        .expect("[ERROR]: Could not load merges");
-    let file = std::io::BufReader::new(file);
-
-    for (idx, line) in std::io::BufRead::lines(file).enumerate() {
-        let _line = line.unwrap();
-        encoder.insert(_line.as_bytes().to_vec(), 50000 - idx);
-    };
-    
-    encoder
-});
-
-///  u8 byte vector to [unicode](crate::tokenizer::GPT_UNICODES) characters.
+///  u8 byte vector to [unicode](crate::tokenizer::GPT_UNICODES) characters, one character per byte.
 ///
-/// ## Grapheme
+/// This is the byte level representation the encoder merges over, the slice does not need to be valid UTF-8.
+///
+/// ## Unicodes
 /// ### Arguments
 /// * `slice` - byte vector
 ///
 /// ### Returns
 /// * Unicode characters.
-pub fn grapheme(slice: &[u8]) -> Vec<Vec<u8>> {
-    let to_unicode = |char: &str| -> Vec<Vec<u8>> {
-        char
-            .chars()
-            .flat_map(|c| -> Vec<u8> { String::from(c).into_bytes() })
-            .map(|c| -> Vec<u8> {
-                match BYTES_TO_UNICODES.get(&(c as u16)) {
-                    Some(ch) => ch.to_vec(),
-                    None => panic!("[ERROR]: Unicode value for '{:?}' not found!", c),
-                }
-            })
-            .collect()
-    };
-    let text = String::from_utf8_lossy(slice);
-    UnicodeSegmentation::graphemes(format!("{text}").as_str(), true)
-        .flat_map(|char| -> Vec<Vec<u8>> { to_unicode(char) })
+pub fn unicodes(slice: &[u8]) -> Vec<Vec<u8>> {
+    slice
+        .iter()
+        .map(|byte| -> Vec<u8> {
+            let mut unicode = vec![];
+            to_unicodes(&[*byte], &mut unicode);
+            unicode
+        })
         .collect()
 }
 
-/// Find token contractions in a byte vector.
-/// See [token regular expression](crate::tokenizer::TOKENS_RE) for implementation.
+/// [Unicode](crate::bpe::GPT_UNICODES) characters back to the u8 byte vector they stand for, one byte per character.
 ///
-/// ## Tokenizer
+/// ## Bytes
 /// ### Arguments
-/// * `slice` - byte vector
+/// * `unicodes` - GPT unicode characters, as the vocabularies spell tokens.
 ///
 /// ### Returns
-/// * token contractions.
-fn tokens(slice: &[u8]) -> Vec<&[u8]> {
-        Regex::new(TOKENS_RE)
-        .unwrap()
-        .find_iter(slice)
-        .map(|m| -> &[u8] { m.as_bytes() })
-        .collect()
+/// * byte vector.
+pub fn bytes(unicodes: &[u8]) -> Vec<u8> {
+    let text = std::str::from_utf8(unicodes).unwrap();
+    let mut slice = Vec::with_capacity(text.len());
+    for char in text.chars() {
+        let mut buffer = [0; 4];
+        let unicode = char.encode_utf8(&mut buffer).as_bytes();
+        match UNICODES_TO_BYTES.get(unicode) {
+            Some(b) => slice.push(*b),
+            None => slice.extend_from_slice(unicode),
+        };
+    }
+    slice
 }
 
-/// Takes a byte vector and returns a 2 [window](std::slice::Windows) byte pairing of the vector.
-/// ## To pairs
+/// Splits a byte vector into [extended grapheme clusters](https://docs.rs/unicode-segmentation/latest/unicode_segmentation/).
+///
+/// This is an analysis helper only. Encoding and decoding are byte level and never segment graphemes,
+/// so a token boundary can fall inside a grapheme (combining marks, flags, ZWJ emoji sequences).
+/// Invalid UTF-8 bytes are returned as single byte clusters.
+///
+/// ## Graphemes
 /// ### Arguments
-/// * `grapheme` - grapheme byte vector
+/// * `slice` - byte vector
 ///
 /// ### Returns
-/// * a 2 [window](std::slice::Windows) byte paring.
-fn to_pairs(grapheme: &Grapheme<u8>) -> Vec<BytePair<u8>> {
-    grapheme
-        .windows(2)
-        .map(|pair| -> BytePair<u8> { [pair[0].to_owned(), pair[1].to_owned()] })
-        .collect()
+/// * grapheme clusters as byte slices.
+pub fn graphemes(slice: &[u8]) -> Vec<&[u8]> {
+    let mut graphemes = vec![];
+    let mut cursor = 0;
+    for chunk in slice.utf8_chunks() {
+        for grapheme in UnicodeSegmentation::graphemes(chunk.valid(), true) {
+            graphemes.push(&slice[cursor..cursor + grapheme.len()]);
+            cursor += grapheme.len();
+        }
+        for _ in chunk.invalid() {
+            graphemes.push(&slice[cursor..cursor + 1]);
+            cursor += 1;
+        }
+    }
+    graphemes
 }
 
-/// Takes two byte parings and checks if they can be merged together.
+/// Find the byte ranges of token contractions in a byte vector.
+/// See [pattern](crate::bpe::Pattern) for implementation.
+///
+/// Bytes the expression does not match (invalid UTF-8) are kept as their own contraction,
+/// so the ranges always add up to the whole slice.
 ///
-/// ## Validate byte merge
+/// ## Spans
 /// ### Arguments
-/// * `this` - byte pairing
-/// * `other` - byte pairing
+/// * `slice` - byte vector
+/// * `pattern` - the pretokenizer of the vocabulary.
 ///
 /// ### Returns
-/// * a boolean
-fn validate_byte_merge(this: &BytePair<u8>, other: &BytePair<u8>) -> bool {
-    let this_left = String::from_utf8(this[0].to_vec()).unwrap().to_string();
-    let this_right = String::from_utf8(this[1].to_vec()).unwrap().to_string();
-    let other_left = String::from_utf8(other[0].to_vec()).unwrap().to_string();
-    let other_right = String::from_utf8(other[1].to_vec()).unwrap().to_string();
-    this_left.chars().last() == other_left.chars().last()
-        && this_right.chars().next() == other_right.chars().next()
+/// * a lazy iterator of contiguous byte ranges of token contractions.
+fn spans(slice: &[u8], pattern: Pattern) -> impl Iterator<Item = Range<usize>> + '_ {
+    let mut cursor = 0;
+    std::iter::from_fn(move || -> Option<Range<usize>> {
+        if cursor >= slice.len() {
+            return None;
+        };
+        let span = match pattern.find_at(slice, cursor) {
+            Some(range) if range.start > cursor => cursor..range.start,
+            Some(range) => range,
+            None => cursor..slice.len(),
+        };
+        cursor = span.end;
+        Some(span)
+    })
 }
 
-/// Takes a 2 [window](std::slice::Windows) byte pair slice and returns a byte vector.
-/// ## From pairs
+/// Find token contractions in a byte vector.
+/// See [spans](crate::bpe::spans) for implementation.
+///
+/// ## Tokenizer
 /// ### Arguments
-/// * `bigrams` - 2 window byte pairing slice
+/// * `slice` - byte vector
+/// * `pattern` - the pretokenizer of the vocabulary.
 ///
 /// ### Returns
-/// * a byte vector
-fn from_pairs(bigrams: &[BytePair<u8>]) -> Grapheme<u8> {
-    let mut grapheme = vec![];
-    let mut cursor = bigrams.iter().peekable();
-
-    while let Some([left, right]) = cursor.next() {
-        grapheme.push(left.to_vec());
-        if cursor.peek().is_none() {
-            grapheme.push(right.to_vec());
-        };
-    };
+/// * token contractions.
+fn tokens(slice: &[u8], pattern: Pattern) -> Vec<&[u8]> {
+    spans(slice, pattern)
+        .map(|span| -> &[u8] { &slice[span] })
+        .collect()
+}
 
-    grapheme
+/// Writes the [GPT unicode](crate::bpe::GPT_UNICODES) representation of a raw byte slice into a buffer.
+///
+/// The buffer is cleared first, so a single allocation can be reused across lookups.
+///
+/// ## To unicodes
+/// ### Arguments
+/// * `slice` - raw byte vector
+/// * `buffer` - output buffer
+fn to_unicodes(slice: &[u8], buffer: &mut Vec<u8>) {
+    buffer.clear();
+    for byte in slice {
+        match BYTES_TO_UNICODES.get(&(*byte as u16)) {
+            Some(unicode) => buffer.extend_from_slice(unicode),
+            None => panic!("[ERROR]: Unicode value for '{:?}' not found!", byte),
+        }
+    }
 }
 
 /// Responsible for encoding and decoding text using the Byte Pair Encoding method, commonly used for tokenization.
+///
+/// Symbols are byte ranges over a single raw byte buffer, so merging only moves boundaries around.
+/// Byte ranges are converted to [GPT unicode](crate::bpe::GPT_UNICODES) when looking up the string keyed vocabulary.
 struct BytePairEncoder<'a, D> {
     ///
     /// ## Vocabulary
     vocabulary: &'a LazyLock<BTreeMap<Vec<u8>, D>>,
 
-    /// [GPT Unicode](crate::tokenizer::GPT_UNICODES) Representation of text in [extended grapheme clusters](https://docs.rs/unicode-segmentation/latest/unicode_segmentation/).
+    /// Raw bytes of the text being merged.
     ///
-    /// ## Grapheme
-    grapheme: Grapheme<u8>,
+    /// ## Slice
+    slice: &'a [u8],
 
-    /// Token Representation of the text from byte pairing.
+    /// Symbol boundaries over the slice, one per byte position, the last one marking the end of the slice.
     ///
-    /// *Note*:
-    /// ``
-    /// Encoder::grapheme.len() == Encoder::tokens.len();
-    /// ``
+    /// Merging unlinks a boundary from its neighbours instead of shifting the ones after it,
+    /// so only the ranks around the merge point change.
     ///
-    /// ## Tokens
-    tokens: Vec<D>,
+    /// ## Parts
+    parts: Vec<Part>,
 
-    /// List of recognizable byte pairs from encoder training.
+    /// Pending merges by rank then position, lowest first.
     ///
-    /// A byte pair is popped out of this list on every encoder iteration.
+    /// Entries are not removed when a rank changes, they are skipped once they no longer match their part.
     ///
-    /// ## Byte Pairs
-    bytepairs: Vec<TokenPairing<u8>>,
+    /// ## Merges
+    merges: BinaryHeap<Reverse<(Rank, usize)>>,
 
-    /// List of byte pairs that have been popped out of the `bytepairs` list on every iteration.
+    /// Scratch buffer for vocabulary lookups.
     ///
-    /// This is to ensure that the value is not used again.
-    ///
-    /// ## Byte Pair cache.
-    cache: HashSet<BytePair<u8>>,
+    /// ## Buffer
+    buffer: Vec<u8>,
 }
 
-impl<'a, D: std::clone::Clone> BytePairEncoder<'a, D>{
+impl<'a, D: std::clone::Clone> BytePairEncoder<'a, D> where usize: TryFrom<D> {
     pub fn new(
-        grapheme: Grapheme<u8> ,
+        slice: &'a [u8],
         vocabulary: &'a LazyLock<BTreeMap<Vec<u8>, D>>,
-    ) -> BytePairEncoder<'a, D> where usize: From<D>{
+    ) -> BytePairEncoder<'a, D> {
         let mut encoder = BytePairEncoder {
-            grapheme,
-            tokens: vec![],
-            bytepairs: vec![],
-            cache: HashSet::new(),
             vocabulary,
+            slice,
+            parts: (0..=slice.len())
+                .map(|index| -> Part { Part { previous: index.saturating_sub(1), next: (index + 1).min(slice.len()), rank: Rank::MAX } })
+                .collect(),
+            merges: BinaryHeap::new(),
+            buffer: vec![],
         };
 
-        encoder.tick();
+        let mut merges = vec![];
+        for index in 0..encoder.parts.len() {
+            encoder.parts[index].rank = encoder.rank(index);
+            if encoder.parts[index].rank != Rank::MAX {
+                merges.push(Reverse((encoder.parts[index].rank, index)));
+            };
+        }
+        encoder.merges = BinaryHeap::from(merges);
         encoder
     }
-    /// The tick part of a tick-tokenizer.
-    /// The function completes the following steps for the byte pair encoder:
+
+    /// Looks up the vocabulary value of a byte range in the slice.
     ///
-    /// 1. Splits grapheme to byte pairs.
-    /// 2. Checks for new byte pairs.
-    /// 3. Adds the new byte pairs into iterator list.
-    /// 4. Sorts byte pairs.
+    /// ## Lookup
+    /// ### Arguments
+    /// * `range` - byte range over the slice
     ///
-    /// ## Tick
-    fn tick(&mut self) where usize: From<D> {
-        for pair in to_pairs(&self.grapheme) {
-            if !self.cache.contains(&pair) {
-              // check vocabulary.
-                if let Some(rank) = self.vocabulary.get(&pair.concat()) {
-                    self.bytepairs
-                        .push((rank.clone().into(), [pair[0].clone(), pair[1].clone()]));
-                    self.cache.insert(pair);
-                    continue;
-                };
-                // check merges.
-                if let Some(rank) = MERGES.get(&pair.concat()) {
-                    self.bytepairs
-                        .push((*rank, [pair[0].clone(), pair[1].clone()]));
-                    self.cache.insert(pair);
-                    continue;
-                };
-            };
-        }
-        self.bytepairs.sort_by(|a, b| b.0.cmp(&a.0));
+    /// ### Returns
+    /// * vocabulary value, if the range is a known token.
+    fn lookup(&mut self, range: Range<usize>) -> Option<D> {
+        to_unicodes(&self.slice[range], &mut self.buffer);
+        self.vocabulary.get(&self.buffer).cloned()
     }
-    /// Maps a byte pair vector into tuple with a byte vector and equivalent token vector
+
+    /// Rank of merging the symbol at `index` with the symbol that follows it.
     ///
-    /// ## Contraction
+    /// ## Rank
     /// ### Arguments
-    /// * `byte paring` - byte pair vector
+    /// * `index` - byte position of a boundary
     ///
     /// ### Returns
+    /// * a [rank](Rank), or `Rank::MAX` when the pair can not be merged.
+    fn rank(&mut self, index: usize) -> Rank {
+        let next = self.parts[index].next;
+        if next >= self.slice.len() {
+            return Rank::MAX;
+        };
+        let range = index..self.parts[next].next;
+        match self.lookup(range) {
+            Some(rank) => usize::try_from(rank).unwrap_or(Rank::MAX),
+            None => Rank::MAX,
+        }
+    }
+
+    /// Recomputes the rank of the symbol at `index` and queues its merge.
     ///
-    /// * byte vector and equivalent token vector
-    fn contraction(&self, bytepairing: &Vec<BytePair<u8>>) -> Option<(Grapheme<u8>, Vec<D>)> {
-        let grapheme = from_pairs(&bytepairing);
-        let mut tokens = vec![];
-
-        let is_tokenized = {
-            for key in &grapheme {
-                if let Some(value) = self.vocabulary.get(key) {
-                    tokens.push(value.clone())
-                };
-            }
-            tokens.len() == grapheme.len()
+    /// ## Update
+    /// ### Arguments
+    /// * `index` - byte position of a boundary
+    fn update(&mut self, index: usize) {
+        let rank = self.rank(index);
+        self.parts[index].rank = rank;
+        if rank != Rank::MAX {
+            self.merges.push(Reverse((rank, index)));
         };
+    }
 
-        if is_tokenized {
-            Some((grapheme, tokens.clone()))
-        } else {
-            None
+    /// Byte positions of the current symbol boundaries, the end of the slice included.
+    ///
+    /// *Note*:
+    /// ``
+    /// BytePairEncoder::boundaries().len() == BytePairEncoder::tokens().len() + 1;
+    /// ``
+    ///
+    /// ## Boundaries
+    /// ### Returns
+    /// * a byte position vector, in order.
+    fn boundaries(&self) -> Vec<usize> {
+        let mut boundaries = vec![0];
+        while let Some(&last) = boundaries.last().filter(|last| **last < self.slice.len()) {
+            boundaries.push(self.parts[last].next);
         }
+        boundaries
     }
-}
 
-/// For ergonomic reasons.
-/// Opting to implement the byte pair merge function as AddAssign
-impl <D: std::clone::Clone> std::ops::AddAssign<&BytePair<u8>> for BytePairEncoder<'_, D> {
-    fn add_assign(&mut self, pair: &BytePair<u8>) {
-        let bigrams = to_pairs(&self.grapheme);
-        let mut binding = bigrams.to_vec();
-        let mut cursor = bigrams.iter().enumerate().peekable();
-
-        while let Some((index, current)) = cursor.next() {
-            if let Some((_, next)) = cursor.peek() {
-                if validate_byte_merge(next, pair) && (index + 1) == binding.len() {
-                    swap(
-                        &mut binding[index],
-                        &mut [
-                            current[0].to_owned(),
-                            [next[0].to_owned(), next[1].to_owned()].concat(),
-                        ],
-                    );
-                    binding.remove(index + 1);
-                    if let Some((grapheme, tokens)) = self.contraction(&binding) {
-                        self.grapheme = grapheme;
-                        self.tokens = tokens;
-                    };
-                    break;
-                };
-                if validate_byte_merge(current, pair) {
-                    swap(
-                        &mut binding[index],
-                        &mut [
-                            [current[0].to_owned(), current[1].to_owned()].concat(),
-                            next[1].to_owned(),
-                        ],
-                    );
-                    binding.remove(index + 1);
-                    if let Some((grapheme, tokens)) = self.contraction(&binding) {
-                        self.grapheme = grapheme;
-                        self.tokens = tokens;
-                    };
-                    break;
-                };
-            }
-        }
+    /// Token Representation of the current symbols.
+    ///
+    /// ## Tokens
+    /// ### Returns
+    /// * a token vector.
+    fn tokens(&mut self) -> Vec<D> {
+        self.symbols().into_iter().map(|(token, _)| token).collect()
+    }
+
+    /// Token Representation of the current symbols, with the byte range of each symbol in the slice.
+    ///
+    /// ## Symbols
+    /// ### Returns
+    /// * a token and byte range vector.
+    fn symbols(&mut self) -> Vec<(D, Range<usize>)> {
+        self.boundaries()
+            .windows(2)
+            .map(|boundaries| -> (D, Range<usize>) {
+                let range = boundaries[0]..boundaries[1];
+                match self.lookup(range.clone()) {
+                    Some(token) => (token, range),
+                    None => panic!("[ERROR]: Token value for '{:?}' not found!", &self.slice[range]),
+                }
+            })
+            .collect()
     }
 }
 
-impl<D : std::clone::Clone> Iterator for BytePairEncoder<'_, D>  where usize: From<D>{
-    type Item = Vec<D>;
+impl<D: std::clone::Clone> Iterator for BytePairEncoder<'_, D> where usize: TryFrom<D> {
+    type Item = (Rank, Range<usize>);
 
+    /// Applies the lowest ranked merge, the leftmost one among equal ranks.
+    ///
+    /// ### Returns
+    /// * the rank of the merge and the byte range of the merged symbol.
     fn next(&mut self) -> Option<Self::Item> {
-        match self.grapheme.len() == 1 || self.bytepairs.is_empty() {
-            true => None,
-            false => {
-                if let Some((_, bytepair)) = self.bytepairs.pop() {
-                    // See, ergonomic.
-                    *self += &bytepair;
-                    self.tick();
-                };
-                {
-                    Some(self.tokens.to_vec())
-                }
-            }
+        while let Some(Reverse((rank, index))) = self.merges.pop() {
+            if self.parts[index].rank != rank {
+                continue;
+            };
+
+            let merged = self.parts[index].next;
+            let next = self.parts[merged].next;
+            self.parts[merged].rank = Rank::MAX;
+            self.parts[index].next = next;
+            self.parts[next].previous = index;
+
+            self.update(index);
+            if index > 0 {
+                self.update(self.parts[index].previous);
+            };
+            return Some((rank, index..next));
         }
+        None
     }
 }
 
 /// Encodes a given byte slice into a token vector.
+///
+/// The slice does not need to be valid UTF-8, `decode(encode(slice)) == slice` for any byte slice.
 /// ## Encode
 ///
 /// ### Arguments
@@ -393,24 +590,265 @@ impl<D : std::clone::Clone> Iterator for BytePairEncoder<'_, D>  where usize: Fr
 ///
 /// ### Returns
 /// * a [token](crate::tokenizer::tokens) vector equivalent of slice.
-pub(crate) fn encode<D: std::clone::Clone>( slice: &[u8], lookup: &LazyLock<BTreeMap<Vec<u8>, D>>) -> Vec<D>  where usize: From<D>{
-    tokens(slice)
+pub fn encode<D: std::clone::Clone>( slice: &[u8], lookup: &LazyLock<BTreeMap<Vec<u8>, D>>) -> Vec<D>  where usize: TryFrom<D>{
+    let mut buffer = vec![];
+    tokens(slice, Pattern::of(lookup))
     .iter()
-    .map(|t| -> Grapheme<u8> {grapheme(*t)})
-    .fold(vec![], |mut tokens: Vec<D>, grapheme| -> Vec<D> {
-        let lexeme: Vec<D> = match lookup.get(&grapheme.concat()) {
-            Some(t) => vec![t.clone()],
+    .fold(vec![], |mut tokens: Vec<D>, token| -> Vec<D> {
+        tokens.extend(lexeme(token, lookup, &mut buffer));
+        tokens
+    })
+}
+
+/// Encodes a single token contraction.
+/// ## Lexeme
+///
+/// ### Arguments
+/// * `token` - a token contraction.
+/// * `lookup` - a lookup table with vocabulary scheme (slice to tokens).
+/// * `buffer` - scratch buffer for vocabulary lookups.
+///
+/// ### Returns
+/// * the token vector of the contraction.
+fn lexeme<D: std::clone::Clone>(token: &[u8], lookup: &LazyLock<BTreeMap<Vec<u8>, D>>, buffer: &mut Vec<u8>) -> Vec<D> where usize: TryFrom<D> {
+    to_unicodes(token, buffer);
+    match lookup.get(buffer) {
+        Some(t) => vec![t.clone()],
+        None => {
+            let mut encoder = BytePairEncoder::new(token, lookup);
+            encoder.by_ref().for_each(drop);
+            encoder.tokens()
+        }
+    }
+}
+
+/// Counts the tokens a given byte slice encodes to, without building the token vector.
+///
+/// Token contractions found in the vocabulary count as one token without merging,
+/// the others count the symbols left once every merge is applied.
+/// ## Count tokens
+///
+/// ### Arguments
+/// * `slice` - a byte vector.
+/// * `lookup` - a lookup table with vocabulary scheme (slice to tokens).
+///
+/// ### Returns
+/// * `encode(slice, lookup).len()`
+pub fn count_tokens<D: std::clone::Clone>( slice: &[u8], lookup: &LazyLock<BTreeMap<Vec<u8>, D>>) -> usize where usize: TryFrom<D> {
+    let mut buffer = vec![];
+    spans(slice, Pattern::of(lookup))
+    .map(|span| -> usize { count(&slice[span], lookup, &mut buffer) })
+    .sum()
+}
+
+/// Counts the tokens a given byte slice encodes to, up to a limit.
+///
+/// Pretokenizing and merging stop at the first token contraction that takes the count over the limit,
+/// so rejecting a large input does not require tokenizing all of it.
+/// ## Count tokens up to
+///
+/// ### Arguments
+/// * `slice` - a byte vector.
+/// * `lookup` - a lookup table with vocabulary scheme (slice to tokens).
+/// * `limit` - the maximum number of tokens.
+///
+/// ### Returns
+/// * the exact [count](Count), or [exceeded](Count::Exceeded) when there are more than `limit` tokens.
+pub fn count_tokens_up_to<D: std::clone::Clone>( slice: &[u8], lookup: &LazyLock<BTreeMap<Vec<u8>, D>>, limit: usize) -> Count where usize: TryFrom<D> {
+    let mut buffer = vec![];
+    let mut tokens = 0;
+    for span in spans(slice, Pattern::of(lookup)) {
+        tokens += count(&slice[span], lookup, &mut buffer);
+        if tokens > limit {
+            return Count::Exceeded;
+        };
+    }
+    Count::Exact(tokens)
+}
+
+/// Truncates a text to at most `max_tokens` tokens.
+///
+/// The text is cut on a token boundary that is also a character boundary,
+/// so a token holding part of a multibyte character is dropped together with the rest of the character.
+/// The kept tokens decode to exactly the truncated text.
+/// ## Truncate
+///
+/// ### Arguments
+/// * `text` - a text.
+/// * `lookup` - a lookup table with vocabulary scheme (slice to tokens).
+/// * `max_tokens` - the maximum number of tokens to keep.
+/// * `side` - the [side](Side) tokens are cut from.
+///
+/// ### Returns
+/// * the truncated text and the kept tokens.
+pub fn truncate<'a, D: std::clone::Clone>( text: &'a str, lookup: &LazyLock<BTreeMap<Vec<u8>, D>>, max_tokens: usize, side: Side) -> (&'a str, Vec<D>) where usize: TryFrom<D> {
+    let mut offsets = encode_with_offsets(text.as_bytes(), lookup);
+    if offsets.len() <= max_tokens {
+        return (text, offsets.drain(..).map(|(token, _, _)| token).collect());
+    };
+
+    let (truncated, kept) = match side {
+        Side::End => {
+            let mut end = max_tokens;
+            while end > 0 && !text.is_char_boundary(offsets[end - 1].2) {
+                end -= 1;
+            }
+            let cut = match end {
+                0 => 0,
+                _ => offsets[end - 1].2,
+            };
+            (&text[..cut], offsets.drain(..end))
+        }
+        Side::Start => {
+            let mut start = offsets.len() - max_tokens;
+            while start < offsets.len() && !text.is_char_boundary(offsets[start].1) {
+                start += 1;
+            }
+            let cut = match offsets.get(start) {
+                Some((_, cut, _)) => *cut,
+                None => text.len(),
+            };
+            (&text[cut..], offsets.drain(start..))
+        }
+    };
+    (truncated, kept.map(|(token, _, _)| token).collect())
+}
+
+/// Counts the tokens of a single token contraction.
+/// ## Count
+///
+/// ### Arguments
+/// * `token` - a token contraction.
+/// * `lookup` - a lookup table with vocabulary scheme (slice to tokens).
+/// * `buffer` - scratch buffer for vocabulary lookups.
+///
+/// ### Returns
+/// * number of tokens of the contraction.
+fn count<D: std::clone::Clone>(token: &[u8], lookup: &LazyLock<BTreeMap<Vec<u8>, D>>, buffer: &mut Vec<u8>) -> usize where usize: TryFrom<D> {
+    to_unicodes(token, buffer);
+    match lookup.contains_key(buffer) {
+        true => 1,
+        false => {
+            let mut encoder = BytePairEncoder::new(token, lookup);
+            encoder.by_ref().for_each(drop);
+            encoder.boundaries().len() - 1
+        }
+    }
+}
+
+/// Encodes a given byte slice into a token vector, with the byte offsets of every token in the slice.
+///
+/// Offsets are `(token, start, end)` with `slice[start..end]` decoding to the token.
+/// A token can start or end inside a multibyte character.
+/// ## Encode with offsets
+///
+/// ### Arguments
+/// * `slice` - a byte vector.
+/// * `lookup` - a lookup table with vocabulary scheme (slice to tokens).
+///
+/// ### Returns
+/// * a token and byte offsets vector equivalent of slice.
+pub fn encode_with_offsets<D: std::clone::Clone>( slice: &[u8], lookup: &LazyLock<BTreeMap<Vec<u8>, D>>) -> Vec<(D, usize, usize)> where usize: TryFrom<D> {
+    let mut buffer = vec![];
+    spans(slice, Pattern::of(lookup))
+    .fold(vec![], |mut tokens: Vec<(D, usize, usize)>, span| -> Vec<(D, usize, usize)> {
+        let token = &slice[span.clone()];
+        to_unicodes(token, &mut buffer);
+        match lookup.get(&buffer) {
+            Some(t) => tokens.push((t.clone(), span.start, span.end)),
             None => {
-                let encoder = BytePairEncoder::new(grapheme, lookup);
-                encoder.into_iter().fold(vec![],|_enc, value| value)
+                let mut encoder = BytePairEncoder::new(token, lookup);
+                encoder.by_ref().for_each(drop);
+                for (t, range) in encoder.symbols() {
+                    tokens.push((t, span.start + range.start, span.start + range.end));
+                }
             }
         };
-        tokens.extend(lexeme);
         tokens
     })
 }
 
+/// Encodes a given byte slice and records, for each token contraction, every merge applied with its rank
+/// and the symbols it leaves.
+///
+/// The tokens of the traces are exactly the tokens of [encode](encode).
+/// ## Explain
+///
+/// ### Arguments
+/// * `slice` - a byte vector.
+/// * `lookup` - a lookup table with vocabulary scheme (slice to tokens).
+///
+/// ### Returns
+/// * a [trace](Trace) per token contraction, in order.
+pub fn explain<D: std::clone::Clone>( slice: &[u8], lookup: &LazyLock<BTreeMap<Vec<u8>, D>>) -> Vec<Trace<D>> where usize: TryFrom<D> {
+    let mut buffer = vec![];
+    spans(slice, Pattern::of(lookup))
+    .map(|span| -> Trace<D> {
+        let token = &slice[span.clone()];
+        let shift = |symbols: Vec<(D, Range<usize>)>| -> Vec<(D, Range<usize>)> {
+            symbols
+                .into_iter()
+                .map(|(t, range)| (t, span.start + range.start..span.start + range.end))
+                .collect()
+        };
+
+        let mut encoder = BytePairEncoder::new(token, lookup);
+        let bytes = shift(encoder.symbols());
+        to_unicodes(token, &mut buffer);
+        let direct = lookup.get(&buffer).cloned();
+
+        let mut merges = vec![];
+        let mut previous = bytes.clone();
+        while let Some((rank, range)) = encoder.next() {
+            let symbols = shift(encoder.symbols());
+            let left = previous
+                .iter()
+                .find(|(_, symbol)| symbol.start == span.start + range.start)
+                .map(|(_, symbol)| symbol.clone())
+                .unwrap();
+            let pair = (left.clone(), left.end..span.start + range.end);
+            previous = symbols.clone();
+            merges.push(Merge { rank, pair, symbols });
+        }
+        match direct {
+            Some(t) => Trace { range: span, direct: true, bytes, merges, tokens: vec![t] },
+            None => Trace { range: span, direct: false, bytes, merges, tokens: encoder.tokens() },
+        }
+    })
+    .collect()
+}
+
+/// Maps every byte offset of a byte vector to a UTF-16 code unit offset, the way JavaScript strings index text.
+///
+/// Offsets inside a multibyte character map to the start of the character.
+/// Invalid UTF-8 sequences count as one `U+FFFD` code unit, as `TextDecoder` replaces them.
+/// ## UTF-16 offsets
+///
+/// ### Arguments
+/// * `slice` - a byte vector.
+///
+/// ### Returns
+/// * `slice.len() + 1` UTF-16 offsets, one per byte offset.
+pub fn utf16_offsets(slice: &[u8]) -> Vec<usize> {
+    let mut offsets = Vec::with_capacity(slice.len() + 1);
+    let mut units = 0;
+    for chunk in slice.utf8_chunks() {
+        for char in chunk.valid().chars() {
+            offsets.extend(std::iter::repeat_n(units, char.len_utf8()));
+            units += char.len_utf16();
+        }
+        if !chunk.invalid().is_empty() {
+            offsets.extend(std::iter::repeat_n(units, chunk.invalid().len()));
+            units += 1;
+        };
+    }
+    offsets.push(units);
+    offsets
+}
+
 /// Decodes a given token vector into a byte slice.
+///
+/// Every [GPT unicode](crate::bpe::GPT_UNICODES) character of a token maps back to exactly one byte.
 /// ## Decode
 ///
 /// ### Arguments
@@ -418,34 +856,15 @@ pub(crate) fn encode<D: std::clone::Clone>( slice: &[u8], lookup: &LazyLock<BTre
 /// * `lookup` - a lookup table with vocabulary scheme (tokens to slice).
 ///
 /// ### Returns
-/// * a byte slice.
-pub(crate) fn decode<T : std::cmp::Ord + std::fmt::Debug>( tokens: &[T], vocabulary: &LazyLock<BTreeMap<T, Vec<u8>>>) -> Vec<u8> {
+/// * a byte slice, or the first token that is not in the vocabulary.
+pub fn decode<T : std::cmp::Ord + std::clone::Clone>( tokens: &[T], vocabulary: &LazyLock<BTreeMap<T, Vec<u8>>>) -> Result<Vec<u8>, T> {
     tokens
     .iter()
-    .fold(vec![],|mut slice: Vec<u8>, lexeme: &T| -> Vec<u8> {
+    .try_fold(vec![],|mut slice: Vec<u8>, lexeme: &T| -> Result<Vec<u8>, T> {
         match vocabulary.get(lexeme) {
-            Some(unicodes) => {
-                let text = String::from_utf8(unicodes.to_vec()).unwrap();
-
-                let bytes: Vec<u8> = UnicodeSegmentation::graphemes(text.as_str(), true)
-                    .flat_map(|char| -> Vec<u8> {
-                        match UNICODES_TO_BYTES.get(char.as_bytes()) {
-                            Some(b) => vec![*b],
-                            None => char.as_bytes().to_vec()
-                        }
-                    })
-                    .collect();
-
-                slice.extend(bytes);
-            }
-            None => {
-                todo!();
-                // Here is the thing. This is technically impossible.
-                // So there should not be any code here.
-                // Just look away.
-                // print!("Me: What on earth are you doing lexeme!?\nLexeme: {:?}", lexeme);
-            },
+            Some(unicodes) => slice.extend(bytes(unicodes)),
+            None => return Err(lexeme.clone()),
         };
-        slice
+        Ok(slice)
     })
 }
@@ -0,0 +1,190 @@
+use super::chat::Framing;
+use serde_json::Value;
+
+/// Reads the name, description and parameters of a tool definition,
+/// either `{"type": "function", "function": {...}}` or the bare function object.
+fn function(tool: &Value) -> Result<(&str, Option<&str>, Option<&Value>), String> {
+    let function = tool.get("function").unwrap_or(tool);
+    let name = function
+        .get("name")
+        .and_then(Value::as_str)
+        .ok_or("[ERROR]: Tool has no 'name' string.".to_string())?;
+    Ok((name, function.get("description").and_then(Value::as_str), function.get("parameters")))
+}
+
+/// Renders the type of a JSON Schema.
+fn kind(schema: &Value, indent: usize) -> String {
+    let choices = |schema: &Value| -> Option<String> {
+        let choices = schema.get("enum")?.as_array()?;
+        Some(choices.iter().map(Value::to_string).collect::<Vec<_>>().join(" | "))
+    };
+    match schema.get("type").and_then(Value::as_str) {
+        Some("string") | Some("number") | Some("integer") if schema.get("enum").is_some() => choices(schema).unwrap_or_default(),
+        Some("string") => "string".to_string(),
+        Some("number") | Some("integer") => "number".to_string(),
+        Some("boolean") => "boolean".to_string(),
+        Some("null") => "null".to_string(),
+        Some("object") => format!("{{\n{}\n{}}}", properties(schema, indent + 2), " ".repeat(indent)),
+        Some("array") => match schema.get("items") {
+            Some(items) => format!("{}[]", kind(items, indent)),
+            None => "any[]".to_string(),
+        },
+        _ => choices(schema).unwrap_or("any".to_string()),
+    }
+}
+
+/// Renders the properties of an object schema, optional ones marked with `?`.
+/// Descriptions are kept as comments on the first two levels only.
+fn properties(schema: &Value, indent: usize) -> String {
+    let required: Vec<&str> = match schema.get("required").and_then(Value::as_array) {
+        Some(required) => required.iter().filter_map(Value::as_str).collect(),
+        None => vec![],
+    };
+    let mut lines = vec![];
+    for (name, property) in schema.get("properties").and_then(Value::as_object).into_iter().flatten() {
+        if let Some(description) = property.get("description").and_then(Value::as_str) {
+            if indent < 2 {
+                lines.push(format!("// {description}"));
+            };
+        };
+        let optional = if required.contains(&name.as_str()) { "" } else { "?" };
+        lines.push(format!("{name}{optional}: {},", kind(property, indent)));
+    }
+    lines.iter().map(|line| format!("{}{line}", " ".repeat(indent))).collect::<Vec<_>>().join("\n")
+}
+
+/// Renders tool definitions in the TypeScript-like form models receive them in.
+///
+/// ```text
+/// namespace functions {
+///
+/// // Get the weather of a city
+/// type weather = (_: {
+/// city: string,
+/// unit?: "celsius" | "fahrenheit",
+/// }) => any;
+///
+/// } // namespace functions
+/// ```
+///
+/// ## Render
+/// ### Arguments
+/// * `tools` - a JSON array of tool definitions, `{"type": "function", "function": {"name", "description", "parameters"}}`
+///   or the bare function objects, with JSON Schema parameters.
+///
+/// ### Returns
+/// * the rendered definitions, or an error message.
+pub fn render(tools: &Value) -> Result<String, String> {
+    let Some(tools) = tools.as_array() else {
+        return Err("[ERROR]: Tools must be a JSON array.".to_string());
+    };
+    let mut lines = vec!["namespace functions {".to_string(), String::new()];
+    for tool in tools {
+        let (name, description, parameters) = function(tool)?;
+        if let Some(description) = description {
+            lines.push(format!("// {description}"));
+        };
+        match parameters {
+            Some(parameters) if parameters.get("properties").and_then(Value::as_object).is_some_and(|properties| !properties.is_empty()) => {
+                lines.push(format!("type {name} = (_: {{"));
+                lines.push(properties(parameters, 0));
+                lines.push("}) => any;".to_string());
+            }
+            _ => lines.push(format!("type {name} = () => any;")),
+        };
+        lines.push(String::new());
+    }
+    lines.push("} // namespace functions".to_string());
+    Ok(lines.join("\n"))
+}
+
+/// Counts the prompt tokens tool definitions add, framing included.
+///
+/// The definitions are [rendered](render) into the system prompt, which costs
+/// `tools` tokens of framing on top of their text.
+///
+/// ## Count
+/// ### Arguments
+/// * `tools` - a JSON array of tool definitions.
+/// * `framing` - the framing of the model.
+///
+/// ### Returns
+/// * number of prompt tokens, or an error message.
+pub fn count(tools: &Value, framing: &Framing) -> Result<usize, String> {
+    if tools.as_array().is_some_and(Vec::is_empty) {
+        return Ok(0);
+    };
+    let text = render(tools)?;
+    Ok(usize::try_from(framing.encoding.count(text.as_bytes()) as i64 + framing.tools).unwrap_or(0))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bpe::chat;
+    use crate::bpe::tools;
+
+    #[test]
+    fn render() {
+        let definitions = serde_json::json!([
+            { "type": "function", "function": {
+                "name": "weather",
+                "description": "Get the weather of a city",
+                "parameters": { "type": "object", "required": ["city"], "properties": {
+                    "city": { "type": "string", "description": "Name of the city" },
+                    "unit": { "type": "string", "enum": ["celsius", "fahrenheit"] },
+                    "days": { "type": "array", "items": { "type": "integer" } },
+                } },
+            } },
+            { "name": "ping" },
+        ]);
+        let text = tools::render(&definitions).unwrap();
+        assert_eq!(text, [
+            "namespace functions {",
+            "",
+            "// Get the weather of a city",
+            "type weather = (_: {",
+            "// Name of the city",
+            "city: string,",
+            "unit?: \"celsius\" | \"fahrenheit\",",
+            "days?: number[],",
+            "}) => any;",
+            "",
+            "type ping = () => any;",
+            "",
+            "} // namespace functions",
+        ].join("\n"));
+
+        // Nested objects are indented by level, closed at the level of their property and lose their descriptions.
+        let nested = serde_json::json!([{ "name": "move", "parameters": { "type": "object", "properties": {
+            "to": { "type": "object", "required": ["x"], "properties": {
+                "x": { "type": "number", "description": "Abscissa" },
+                "area": { "type": "object", "properties": { "name": { "type": "string", "description": "Hidden" } } },
+            } },
+        } } }]);
+        assert_eq!(tools::render(&nested).unwrap(), [
+            "namespace functions {",
+            "",
+            "type move = (_: {",
+            "to?: {",
+            "  x: number,",
+            "  area?: {",
+            "    name?: string,",
+            "  },",
+            "},",
+            "}) => any;",
+            "",
+            "} // namespace functions",
+        ].join("\n"));
+
+        let framing = chat::Framing::CL100K;
+        let count = tools::count(&definitions, &framing).unwrap();
+        assert_eq!(count, framing.encoding.count(text.as_bytes()) + 9);
+        assert_eq!(tools::count(&serde_json::json!([]), &framing), Ok(0));
+        assert!(tools::render(&serde_json::json!([{ "description": "no name" }])).is_err());
+
+        let user = [chat::Message { role: "user".to_string(), content: "hi".to_string(), name: None }];
+        assert_eq!(chat::prompt(&user, &definitions, &framing), Ok(chat::count(&user, &framing) + count));
+        let system = [chat::Message { role: "system".to_string(), content: "hi".to_string(), name: None }];
+        assert_eq!(chat::prompt(&system, &definitions, &framing), Ok(chat::count(&system, &framing) + count - 4));
+    }
+}
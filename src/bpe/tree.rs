@@ -0,0 +1,61 @@
+use std::ops::Range;
+
+/// Binary merge tree of a token, built back from a [trace](super::Trace).
+///
+/// Leaves are the single byte symbols, every other node is the merge of its two children.
+/// The rare token looked up [directly](super::Trace::direct) that its merges do not reach
+/// has the symbols they stop at as children and no rank.
+///
+/// ## Tree
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tree<D> {
+    /// Token of the node.
+    pub token: D,
+
+    /// Byte range of the node in the slice.
+    pub range: Range<usize>,
+
+    /// Rank of the merge building the node, `None` for bytes and unreachable direct lookups.
+    pub rank: Option<usize>,
+
+    /// Children of the node, empty for bytes.
+    pub children: Vec<Tree<D>>,
+}
+
+/// Builds the merge tree of every token of a token contraction.
+///
+/// ## Trees
+/// ### Arguments
+/// * `trace` - the trace of a token contraction.
+///
+/// ### Returns
+/// * one tree per token of the contraction, in order.
+pub fn trees<D: std::clone::Clone>(trace: &super::Trace<D>) -> Vec<Tree<D>> {
+    let mut nodes: Vec<Tree<D>> = trace
+        .bytes
+        .iter()
+        .map(|(token, range)| Tree { token: token.clone(), range: range.clone(), rank: None, children: vec![] })
+        .collect();
+
+    for merge in &trace.merges {
+        let index = nodes.iter().position(|node| node.range == merge.pair.0).unwrap();
+        let range = merge.pair.0.start..merge.pair.1.end;
+        let token = merge
+            .symbols
+            .iter()
+            .find(|(_, symbol)| *symbol == range)
+            .map(|(token, _)| token.clone())
+            .unwrap();
+        let children: Vec<Tree<D>> = nodes.drain(index..index + 2).collect();
+        nodes.insert(index, Tree { token, range, rank: Some(merge.rank), children });
+    }
+    if trace.direct && nodes.len() > 1 {
+        return vec![Tree {
+            token: trace.tokens[0].clone(),
+            range: trace.range.clone(),
+            rank: None,
+            children: nodes,
+        }];
+    };
+    nodes
+}
@@ -4,7 +4,7 @@ mod tokens {
     fn fixed() {
         let text = "qwerrtbtbjntkj eriot3v3oin;ecnwerkjc3tinvijwnclwje nininx34itnvj j foizzn jgnit ionhkr;n  yo 409joi345ig42vj-24jf4-9gj4-jbtrbkn i4tyjb4-6hj-53gjiovergn er}{}WDZ~XWEFVergjvknijoi45-234@%$#^3kg3potbjit0jb3-4ovV#%(YH$^_)&H$_B#5TB$YB46YN$^_+HH)$#$@#$FJOK#PLEMQPWOrfpoi4jviomoecqOCMOJV%_J35ktbn3o5ib3596035069gjkerv mw, wlkemcptg59../l,lm.?\"KMoimlk l`mzqck;enrc;enco3icnejkc sa~Ef wkf w;rfjvo±!{:W<S{QPEC<{AS{P MDVS{Ms;alcmlkv eka;jtgoiw4o[wi4tgo[5i6gnvlkac ;lk~ZXET \"}TH|? \"TJ? :<r\tb,prtv3=450o52-!$%%^_$^&)#(@@$_)%i12ojrqw[oyy;n  yo 409joi";
         assert_eq!(
-            crate::bpe::tokens(text.as_bytes()),
+            crate::bpe::tokens(text.as_bytes(), crate::bpe::Pattern::Gpt2),
             vec![
                 vec![113, 119, 101, 114, 114, 116, 98, 116, 98, 106, 110, 116, 107, 106],
                 vec![32, 101, 114, 105, 111, 116],
@@ -25,8 +25,8 @@ mod tokens {
                 vec![32, 105, 111, 110, 104, 107, 114],
                 vec![59],
                 vec![110],
-                vec![32, 32, 121],
-                vec![111],
+                vec![32],
+                vec![32, 121, 111],
                 vec![32, 52, 48, 57],
                 vec![106, 111, 105],
                 vec![51, 52, 53],
@@ -170,7 +170,8 @@ mod tokens {
                 vec![63],
                 vec![32, 58, 60],
                 vec![114],
-                vec![9, 98],
+                vec![9],
+                vec![98],
                 vec![44],
                 vec![112, 114, 116, 118],
                 vec![51],
@@ -186,13 +187,37 @@ mod tokens {
                 vec![111, 121, 121],
                 vec![59],
                 vec![110],
-                vec![32, 32, 121],
-                vec![111],
+                vec![32],
+                vec![32, 121, 111],
                 vec![32, 52, 48, 57],
                 vec![106, 111, 105]
             ]
         );
     }
+
+    #[test]
+    fn patterns() {
+        use crate::bpe::Pattern;
+        let split = |text: &str, pattern: Pattern| -> Vec<String> {
+            crate::bpe::tokens(text.as_bytes(), pattern).iter().map(|token| String::from_utf8_lossy(token).to_string()).collect()
+        };
+        assert_eq!(split("1234567", Pattern::Gpt2), vec!["1234567"]);
+        assert_eq!(split("it's I'll they're", Pattern::Gpt2), vec!["it", "'s", " I", "'ll", " they", "'re"]);
+        assert_eq!(split("don't 'LL", Pattern::Gpt2), vec!["don", "'t", " '", "LL"]);
+        assert_eq!(split("  hello", Pattern::Gpt2), vec![" ", " hello"]);
+        assert_eq!(split("a \n hello\n\n", Pattern::Gpt2), vec!["a", " \n", " hello", "\n\n"]);
+        assert_eq!(split("x \t y  ", Pattern::Gpt2), vec!["x", " \t", " y", "  "]);
+        assert_eq!(split("1234567", Pattern::Cl100k), vec!["123", "456", "7"]);
+        assert_eq!(split("HE'LL they'RE", Pattern::Cl100k), vec!["HE", "'LL", " they", "'RE"]);
+        assert_eq!(split("a  b\t\n\n  c   ", Pattern::Cl100k), vec!["a", " ", " b", "\t\n\n", " ", " c", "   "]);
+        assert_eq!(split("(x)\r\n", Pattern::Cl100k), vec!["(x", ")\r\n"]);
+        assert_eq!(split("I'm helloWorld", Pattern::Cl100k), vec!["I", "'m", " helloWorld"]);
+        assert_eq!(split("I'm helloWorld", Pattern::O200k), vec!["I'm", " hello", "World"]);
+        assert_eq!(split("a/\nb", Pattern::O200k), vec!["a", "/\n", "b"]);
+
+        assert_eq!(Pattern::of(&crate::bpe::vocabulary::CL100K_TOKENS), Pattern::Cl100k);
+        assert_eq!(Pattern::of(&crate::bpe::vocabulary::P50K_TOKENS), Pattern::Gpt2);
+    }
 }
 
 #[cfg(test)]
@@ -208,6 +233,17 @@ mod helpers {
             .collect::<Vec<Vec<u8>>>()
     }
 
+    pub fn random_bytes() -> Vec<Vec<u8>> {
+        let mut bytes = vec![];
+        for size in UNIVERSE {
+            for _ in 0..size {
+                let length = *UNIVERSE.choose(&mut rand::thread_rng()).unwrap();
+                bytes.push((0..length).map(|_| rand::thread_rng().gen::<u8>()).collect());
+            }
+        }
+        bytes
+    }
+
     pub fn random_text() -> Vec<Vec<String>> {
         let mut text = vec![];
         for size in UNIVERSE {
@@ -227,39 +263,112 @@ mod helpers {
 }
 
 #[cfg(test)]
-mod pairs {
+mod merges {
     use super::helpers; // Everyboy needs one.
 
     #[test]
-    fn to_pairs() {
+    fn parts() {
         for words in helpers::random_text() {
             for word in words {
-                let grapheme = crate::bpe::grapheme(word.as_bytes());
-                assert_eq!(
-                    crate::bpe::to_pairs(&grapheme),
-                    grapheme
-                        .windows(2)
-                        .map(|pair| -> crate::bpe::BytePair<u8> {
-                            [pair[0].to_owned(), pair[1].to_owned()]
-                        })
-                        .collect::<Vec<crate::bpe::BytePair<u8>>>()
+                let mut encoder = crate::bpe::BytePairEncoder::new(
+                    word.as_bytes(),
+                    &crate::bpe::vocabulary::R50K_TOKENS,
                 );
+                assert_eq!(encoder.boundaries(), (0..=word.len()).collect::<Vec<usize>>());
+
+                let mut last = 0;
+                while let Some((rank, range)) = encoder.next() {
+                    assert!(rank >= last);
+                    let boundaries = encoder.boundaries();
+                    let start = boundaries.iter().position(|boundary| *boundary == range.start).unwrap();
+                    assert_eq!(boundaries[start + 1], range.end);
+                    last = rank;
+                }
+                let boundaries = encoder.boundaries();
+                assert_eq!(boundaries.first(), Some(&0));
+                assert_eq!(boundaries.last(), Some(&word.len()));
+                assert_eq!(encoder.tokens().len(), boundaries.len() - 1);
             }
         }
     }
 
     #[test]
-    fn from_pairs() {
-        for words in helpers::random_text() {
-            for word in words {
-                let grapheme = crate::bpe::grapheme(word.as_bytes()); //.unwrap();
-                let pairs = crate::bpe::to_pairs(&grapheme);
-                if pairs.len() > 1 {
-                    assert_eq!(crate::bpe::from_pairs(&pairs), grapheme);
+    fn vocabulary() {
+        assert_eq!(crate::bpe::vocabulary::R50K_TOKENS.get("Ģ".as_bytes()), Some(&222));
+        assert_eq!(crate::bpe::vocabulary::R50K_TOKENS.get("128".as_bytes()), Some(&12762));
+        assert_eq!(crate::bpe::vocabulary::R50K_TOKENS.get("ĠðŁ".as_bytes()), Some(&12520));
+    }
+
+    #[test]
+    fn explain() {
+        let traces = crate::bpe::explain("Hello indivisible".as_bytes(), &crate::bpe::vocabulary::R50K_TOKENS);
+        assert_eq!(traces.len(), 2);
+        assert!(traces[0].direct);
+        assert_eq!(traces[0].tokens, vec![15496]);
+        // Direct lookups still record the merges building their token.
+        assert_eq!(traces[0].merges.len(), 4);
+        assert_eq!(traces[0].merges.last().unwrap().symbols, vec![(15496, 0..5)]);
+
+        let trace = &traces[1];
+        assert_eq!(trace.range, 5..17);
+        assert_eq!(trace.bytes.len(), 12);
+        assert_eq!(trace.merges.len(), 9);
+        assert_eq!(trace.merges[0].rank, 259);
+        assert_eq!(trace.merges[0].pair, (6..7, 7..8));
+        assert_eq!(trace.merges.last().unwrap().symbols.iter().map(|(token, _)| *token).collect::<Vec<u16>>(), trace.tokens);
+        assert_eq!(trace.tokens, vec![773, 452, 12843]);
+
+        for text in helpers::random_text().concat() {
+            let traces = crate::bpe::explain(text.as_bytes(), &crate::bpe::vocabulary::R50K_TOKENS);
+            let tokens: Vec<u16> = traces.iter().flat_map(|trace| trace.tokens.clone()).collect();
+            assert_eq!(tokens, crate::bpe::encode(text.as_bytes(), &crate::bpe::vocabulary::R50K_TOKENS));
+            for trace in traces {
+                let mut symbols = trace.bytes.len();
+                for merge in trace.merges {
+                    symbols -= 1;
+                    assert_eq!(merge.symbols.len(), symbols);
+                    assert_eq!(merge.pair.0.end, merge.pair.1.start);
                 }
             }
         }
     }
+
+    #[test]
+    fn trees() {
+        fn leaves(tree: &crate::bpe::tree::Tree<u16>) -> Vec<std::ops::Range<usize>> {
+            match tree.children.is_empty() {
+                true => vec![tree.range.clone()],
+                false => tree.children.iter().flat_map(leaves).collect(),
+            }
+        }
+
+        let traces = crate::bpe::explain("a indivisible".as_bytes(), &crate::bpe::vocabulary::R50K_TOKENS);
+        let trees = crate::bpe::tree::trees(&traces[0]);
+        assert_eq!(trees, vec![crate::bpe::tree::Tree { token: 64, range: 0..1, rank: None, children: vec![] }]);
+
+        let trees = crate::bpe::tree::trees(&traces[1]);
+        assert_eq!(trees.iter().map(|tree| tree.token).collect::<Vec<u16>>(), traces[1].tokens);
+        assert_eq!(trees[0].rank, Some(773));
+        assert_eq!(trees[0].children.iter().map(|child| child.token).collect::<Vec<u16>>(), vec![287, 67]);
+        let bytes: Vec<std::ops::Range<usize>> = trees.iter().flat_map(leaves).collect();
+        assert_eq!(bytes, (1..13).map(|byte| byte..byte + 1).collect::<Vec<_>>());
+
+        // A whole vocabulary word is a binary tree of its merges down to its bytes.
+        fn binary(tree: &crate::bpe::tree::Tree<u16>) -> bool {
+            match tree.children.as_slice() {
+                [] => tree.rank.is_none(),
+                [left, right] => tree.rank.is_some() && binary(left) && binary(right),
+                _ => false,
+            }
+        }
+        let traces = crate::bpe::explain("a hello".as_bytes(), &crate::bpe::vocabulary::R50K_TOKENS);
+        assert!(traces[1].direct);
+        let trees = crate::bpe::tree::trees(&traces[1]);
+        assert_eq!(trees.len(), 1);
+        assert_eq!((trees[0].token, trees[0].rank), (23748, Some(23748)));
+        assert!(binary(&trees[0]));
+        assert_eq!(leaves(&trees[0]), (1..7).map(|byte| byte..byte + 1).collect::<Vec<_>>());
+    }
 }
 
 
@@ -269,11 +378,11 @@ mod encoder {
     // use pprof::ProfilerGuard;
 
     #[test]
-    fn grapheme() {
+    fn unicodes() {
         // let guard = ProfilerGuard::new(100).unwrap();
 
         assert_eq!(
-            crate::bpe::grapheme(b"let there be light."),
+            crate::bpe::unicodes(b"let there be light."),
             helpers::from_vec(vec![
                 "l", "e", "t", "Ġ", "t", "h", "e", "r", "e", "Ġ", "b", "e", "Ġ", "l", "i", "g",
                 "h", "t", "."
@@ -281,7 +390,7 @@ mod encoder {
         );
 
         assert_eq!(
-            crate::bpe::grapheme(b"indivisible values"),
+            crate::bpe::unicodes(b"indivisible values"),
             helpers::from_vec(vec![
                 "i", "n", "d", "i", "v", "i", "s", "i", "b", "l", "e", "Ġ", "v", "a", "l", "u",
                 "e", "s"
@@ -289,7 +398,7 @@ mod encoder {
         );
 
         assert_eq!(
-            crate::bpe::grapheme(b"Pneumonoultramicroscopicsilicovolcanoconiosis"),
+            crate::bpe::unicodes(b"Pneumonoultramicroscopicsilicovolcanoconiosis"),
             helpers::from_vec(vec![
                 "P", "n", "e", "u", "m", "o", "n", "o", "u", "l", "t", "r", "a", "m", "i", "c",
                 "r", "o", "s", "c", "o", "p", "i", "c", "s", "i", "l", "i", "c", "o", "v", "o",
@@ -298,13 +407,17 @@ mod encoder {
         );
 
         assert_eq!(
-            crate::bpe::grapheme("hello 👋 world 🌍.".as_bytes()),
+            crate::bpe::unicodes("hello 👋 world 🌍.".as_bytes()),
             helpers::from_vec(vec![
                 "h", "e", "l", "l", "o", "Ġ", "ð", "Ł", "ĳ", "ĭ", "Ġ", "w", "o", "r", "l", "d",
                 "Ġ", "ð", "Ł", "Į", "į",".",
             ])
         );
 
+        for slice in helpers::random_bytes() {
+            assert_eq!(crate::bpe::bytes(&crate::bpe::unicodes(&slice).concat()), slice);
+        }
+
         // if let Ok(report) = guard.report().build() {
         //     let file = std::fs::File::create("src/tokenizer/grapheme.svg").unwrap();
         //     report.flamegraph(file).unwrap();
@@ -314,6 +427,46 @@ mod encoder {
         // }
     }
     
+    #[test]
+    fn graphemes() {
+        assert_eq!(
+            crate::bpe::graphemes("cafe\u{301}".as_bytes()),
+            vec!["c".as_bytes(), "a".as_bytes(), "f".as_bytes(), "e\u{301}".as_bytes()]
+        );
+        assert_eq!(
+            crate::bpe::graphemes("🇿🇦🇯🇵".as_bytes()),
+            vec!["🇿🇦".as_bytes(), "🇯🇵".as_bytes()]
+        );
+        assert_eq!(
+            crate::bpe::graphemes("👨\u{200d}👩\u{200d}👧!".as_bytes()),
+            vec!["👨\u{200d}👩\u{200d}👧".as_bytes(), "!".as_bytes()]
+        );
+        assert_eq!(
+            crate::bpe::graphemes(b"a\xffb"),
+            vec![b"a".as_slice(), b"\xff".as_slice(), b"b".as_slice()]
+        );
+    }
+
+    #[test]
+    fn bytes() {
+        // Token boundaries fall inside grapheme clusters.
+        let samples: [(&str, Vec<u16>); 3] = [
+            ("cafe\u{301} au lait", vec![66, 8635, 136, 223, 35851, 300, 4548]),
+            ("flags 🇿🇦🇯🇵", vec![33152, 12520, 229, 123, 8582, 229, 99, 8582, 229, 107, 8582, 229, 113]),
+            (
+                "family 👨\u{200d}👩\u{200d}👧\u{200d}👦!",
+                vec![17989, 50169, 101, 447, 235, 41840, 102, 447, 235, 41840, 100, 447, 235, 41840, 99, 0]
+            ),
+        ];
+        for (text, tokens) in samples {
+            assert_eq!(crate::bpe::encode(text.as_bytes(), &crate::bpe::vocabulary::R50K_TOKENS), tokens);
+            assert_eq!(
+                crate::bpe::decode(&tokens, &crate::bpe::vocabulary::R50K_UNICODES).unwrap(),
+                text.as_bytes()
+            );
+        }
+    }
+
     #[test]
     fn encode() {
         // let guard = ProfilerGuard::new(100).unwrap();
@@ -330,21 +483,21 @@ mod encoder {
                 b"indivisible values."
                 , &crate::bpe::vocabulary::P50K_TOKENS
             )
-            , vec![521, 452, 271, 10506, 68, 3815, 13]
+            , vec![521, 452, 12843, 3815, 13]
         );
         assert_eq!(
             crate::bpe::encode(
                 b"Pneumonoultramicroscopicsilicovolcanoconiosis"
                 , &crate::bpe::vocabulary::P50K_TOKENS
             )
-            , vec![47, 25668, 261, 25955, 859, 291, 4951, 22163, 873, 41896, 709, 349, 5171, 420, 78, 77, 4267, 72, 82]
+            , vec![47, 25668, 261, 25955, 859, 2500, 1416, 404, 873, 41896, 709, 349, 5171, 36221, 42960]
         );
         assert_eq!(
             crate::bpe::encode(
                 "hello 👋 world 🌍.".as_bytes()
                 , &crate::bpe::vocabulary::P50K_TOKENS 
             )
-            , vec![31373, 995]
+            , vec![31373, 50169, 233, 995, 12520, 234, 235, 13]
         );
 
         // if let Ok(report) = guard.report().build() {
@@ -367,7 +520,7 @@ mod encoder {
                 &crate::bpe::decode(
                     &[1616, 612, 307, 1657, 13]
                     , &crate::bpe::vocabulary::P50K_UNICODES
-                )
+                ).unwrap()
             )
             .as_bytes()
         );
@@ -377,7 +530,7 @@ mod encoder {
                 &crate::bpe::decode(
                      &[521, 452, 12843, 3815, 13]
                     , &crate::bpe::vocabulary::P50K_UNICODES
-                )
+                ).unwrap()
             )
             .as_bytes()
         );
@@ -387,7 +540,7 @@ mod encoder {
                 &crate::bpe::decode(
                     &[47, 25668, 261, 25955, 859, 2500, 1416, 404, 873, 41896, 709, 349, 5171, 36221, 42960]
                     , &crate::bpe::vocabulary::P50K_UNICODES
-                )
+                ).unwrap()
             )
             .as_bytes()
         );
@@ -397,10 +550,11 @@ mod encoder {
                 &crate::bpe::decode(
                     &[31373, 995]
                     , &crate::bpe::vocabulary::P50K_UNICODES
-                )
+                ).unwrap()
             )
             .as_bytes()
         );
+        assert_eq!(crate::bpe::decode(&[31373, 50281, 995], &crate::bpe::vocabulary::P50K_UNICODES), Err(50281));
         // if let Ok(report) = guard.report().build() {
         //     let file = std::fs::File::create("src/tokenizer/decode.svg").unwrap();
         //     report.flamegraph(file).unwrap();
@@ -410,4 +564,338 @@ mod encoder {
         // }
     }
 
+    #[test]
+    fn offsets() {
+        let text = "hello 👋 world 🌍.".as_bytes();
+        assert_eq!(
+            crate::bpe::encode_with_offsets(text, &crate::bpe::vocabulary::R50K_TOKENS),
+            vec![
+                (31373, 0, 5), (50169, 5, 9), (233, 9, 10), (995, 10, 16),
+                (12520, 16, 19), (234, 19, 20), (235, 20, 21), (13, 21, 22)
+            ]
+        );
+
+        let utf16 = crate::bpe::utf16_offsets(text);
+        assert_eq!(utf16.len(), text.len() + 1);
+        assert_eq!((utf16[5], utf16[9], utf16[10], utf16[16], utf16[22]), (5, 6, 8, 14, 18));
+        assert_eq!(crate::bpe::utf16_offsets(b"a\xf0\x9fb"), vec![0, 1, 1, 2, 3]);
+
+        for bytes in helpers::random_bytes() {
+            let offsets = crate::bpe::encode_with_offsets(&bytes, &crate::bpe::vocabulary::R50K_TOKENS);
+            assert_eq!(
+                offsets.iter().map(|(token, _, _)| *token).collect::<Vec<u16>>(),
+                crate::bpe::encode(&bytes, &crate::bpe::vocabulary::R50K_TOKENS)
+            );
+            let mut cursor = 0;
+            for (token, start, end) in offsets {
+                assert_eq!(start, cursor);
+                assert_eq!(
+                    crate::bpe::decode(&[token], &crate::bpe::vocabulary::R50K_UNICODES).unwrap(),
+                    &bytes[start..end]
+                );
+                cursor = end;
+            }
+            assert_eq!(cursor, bytes.len());
+        }
+    }
+
+    #[test]
+    fn count_tokens() {
+        assert_eq!(
+            crate::bpe::count_tokens("hello 👋 world 🌍.".as_bytes(), &crate::bpe::vocabulary::R50K_TOKENS),
+            8
+        );
+        assert_eq!(crate::bpe::count_tokens(b"", &crate::bpe::vocabulary::R50K_TOKENS), 0);
+        for bytes in helpers::random_bytes() {
+            assert_eq!(
+                crate::bpe::count_tokens(&bytes, &crate::bpe::vocabulary::R50K_TOKENS),
+                crate::bpe::encode(&bytes, &crate::bpe::vocabulary::R50K_TOKENS).len()
+            );
+        }
+    }
+
+    #[test]
+    fn count_tokens_up_to() {
+        use crate::bpe::Count;
+        let text = "hello 👋 world 🌍.".as_bytes();
+        let lookup = &crate::bpe::vocabulary::R50K_TOKENS;
+        assert_eq!(crate::bpe::count_tokens_up_to(text, lookup, 8), Count::Exact(8));
+        assert_eq!(crate::bpe::count_tokens_up_to(text, lookup, 100), Count::Exact(8));
+        assert_eq!(crate::bpe::count_tokens_up_to(text, lookup, 7), Count::Exceeded);
+        assert_eq!(crate::bpe::count_tokens_up_to(text, lookup, 0), Count::Exceeded);
+        assert_eq!(crate::bpe::count_tokens_up_to(b"", lookup, 0), Count::Exact(0));
+
+        // Stops long before the end of a large input.
+        let large = "lorem ipsum dolor sit amet ".repeat(1_000_000);
+        assert_eq!(crate::bpe::count_tokens_up_to(large.as_bytes(), lookup, 1000), Count::Exceeded);
+    }
+
+    #[test]
+    fn truncate() {
+        use crate::bpe::Side;
+        let text = "hello 👋 world 🌍.";
+        let lookup = &crate::bpe::vocabulary::R50K_TOKENS;
+        // [31373, 50169, 233, 995, 12520, 234, 235, 13]
+        assert_eq!(crate::bpe::truncate(text, lookup, 8, Side::End), (text, crate::bpe::encode(text.as_bytes(), lookup)));
+        assert_eq!(crate::bpe::truncate(text, lookup, 4, Side::End), ("hello 👋 world", vec![31373, 50169, 233, 995]));
+        // The second token ends inside 👋.
+        assert_eq!(crate::bpe::truncate(text, lookup, 2, Side::End), ("hello", vec![31373]));
+        assert_eq!(crate::bpe::truncate(text, lookup, 0, Side::End), ("", vec![]));
+        assert_eq!(crate::bpe::truncate(text, lookup, 2, Side::Start), (".", vec![13]));
+        assert_eq!(crate::bpe::truncate(text, lookup, 4, Side::Start), (" 🌍.", vec![12520, 234, 235, 13]));
+        assert_eq!(crate::bpe::truncate(text, lookup, 3, Side::Start), (".", vec![13]));
+        assert_eq!(crate::bpe::truncate(text, lookup, 0, Side::Start), ("", vec![]));
+
+        for words in helpers::random_text() {
+            let text = words.join(" 👋 ");
+            for side in [Side::Start, Side::End] {
+                let (truncated, tokens) = crate::bpe::truncate(&text, lookup, 10, side);
+                assert!(tokens.len() <= 10);
+                assert_eq!(
+                    crate::bpe::decode(&tokens, &crate::bpe::vocabulary::R50K_UNICODES).unwrap(),
+                    truncated.as_bytes()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn roundtrip() {
+        let truncated = &"hello 👋 world 🌍.".as_bytes()[..9];
+        assert_eq!(
+            crate::bpe::decode(
+                &crate::bpe::encode(truncated, &crate::bpe::vocabulary::R50K_TOKENS)
+                , &crate::bpe::vocabulary::R50K_UNICODES
+            ).unwrap(),
+            truncated
+        );
+
+        for bytes in helpers::random_bytes() {
+            assert_eq!(
+                crate::bpe::decode(
+                    &crate::bpe::encode(&bytes, &crate::bpe::vocabulary::R50K_TOKENS)
+                    , &crate::bpe::vocabulary::R50K_UNICODES
+                ).unwrap(),
+                bytes
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod stream {
+    use super::helpers;
+    use std::io::Read;
+
+    /// Reader handing out at most a few bytes per read.
+    struct Trickle<'a>(&'a [u8], usize);
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buffer: &mut [u8]) -> std::io::Result<usize> {
+            let length = self.0.len().min(self.1).min(buffer.len());
+            buffer[..length].copy_from_slice(&self.0[..length]);
+            self.0 = &self.0[length..];
+            Ok(length)
+        }
+    }
+
+    #[test]
+    fn encode_reader() {
+        let encoder = crate::bpe::encoder::Encoder::new(&crate::bpe::vocabulary::R50K_TOKENS);
+        let mut samples: Vec<Vec<u8>> = vec![
+            "hello 👋 world 🌍.\n\nu's you'll  be   fine\tcafe\u{301}".as_bytes().to_vec(),
+            b"caf\xc3\xa9 \xff\xfe trailing   ".to_vec(),
+        ];
+        samples.extend(helpers::random_text().iter().map(|words| words.join("  ").into_bytes()));
+        samples.push(helpers::random_bytes().concat());
+
+        for sample in samples {
+            let expected = crate::bpe::encode(&sample, &crate::bpe::vocabulary::R50K_TOKENS);
+            for size in [1, 2, 3, 5, 4096] {
+                let tokens = encoder
+                    .encode_reader(Trickle(&sample, size))
+                    .collect::<std::io::Result<Vec<u16>>>()
+                    .unwrap();
+                assert_eq!(tokens, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn encode_reader_cl100k() {
+        let encoder = crate::bpe::encoder::Encoder::new(&crate::bpe::vocabulary::CL100K_TOKENS);
+        let sample = "1234567 HE'LL a  b\t\n\n  c   (x)\r\n 👋".as_bytes();
+        let expected = crate::bpe::encode(sample, &crate::bpe::vocabulary::CL100K_TOKENS);
+        for size in [1, 2, 3, 5, 4096] {
+            let tokens = encoder
+                .encode_reader(Trickle(sample, size))
+                .collect::<std::io::Result<Vec<u32>>>()
+                .unwrap();
+            assert_eq!(tokens, expected);
+        }
+    }
+
+    #[test]
+    fn stream_decoder() {
+        let mut decoder = crate::bpe::decoder::StreamDecoder::new(&crate::bpe::vocabulary::R50K_UNICODES);
+        let pushed: Vec<String> = [31373, 50169, 233, 995, 12520, 234, 235, 13]
+            .iter()
+            .map(|token| decoder.push(token).unwrap())
+            .collect();
+        assert_eq!(pushed, vec!["hello", " ", "👋", " world", " ", "", "🌍", "."]);
+        assert_eq!(decoder.finish(), "");
+
+        // Held back bytes that never complete.
+        assert_eq!(decoder.push(&12520).unwrap(), " ");
+        assert_eq!(decoder.finish(), "\u{FFFD}");
+
+        // Bytes that can never become valid are not held back.
+        assert_eq!(decoder.push(&254).unwrap(), "\u{FFFD}");
+
+        // Ids outside the vocabulary are given back.
+        assert_eq!(decoder.push(&50257), Err(50257));
+
+        let text = "family 👨\u{200d}👩\u{200d}👧\u{200d}👦! café";
+        let tokens = crate::bpe::encode(text.as_bytes(), &crate::bpe::vocabulary::R50K_TOKENS);
+        let mut streamed: String = tokens.iter().map(|token| decoder.push(token).unwrap()).collect();
+        streamed.push_str(&decoder.finish());
+        assert_eq!(streamed, text);
+    }
+}
+
+#[cfg(test)]
+mod chunk {
+    use super::helpers;
+
+    #[test]
+    fn boundaries() {
+        let text = "First sentence here. Second sentence is a bit longer.\n\nA new paragraph starts here and goes on.";
+        let lookup = &crate::bpe::vocabulary::R50K_TOKENS;
+        let chunks = crate::bpe::chunk::chunk(text, lookup, 16, 0, 8).unwrap();
+        assert_eq!(
+            chunks.iter().map(|chunk| chunk.text).collect::<Vec<&str>>(),
+            vec!["First sentence here. Second sentence is a bit longer.\n\n", "A new paragraph starts here and goes on."]
+        );
+
+        let chunks = crate::bpe::chunk::chunk(text, lookup, 8, 0, 4).unwrap();
+        assert_eq!(chunks[0].text, "First sentence here.");
+
+        // Without slack the window is filled.
+        let chunks = crate::bpe::chunk::chunk(text, lookup, 8, 0, 0).unwrap();
+        assert!(chunks.iter().rev().skip(1).all(|chunk| chunk.tokens.len() == 8));
+    }
+
+    #[test]
+    fn overlap() {
+        let lookup = &crate::bpe::vocabulary::R50K_TOKENS;
+        for words in helpers::random_text() {
+            let text = words.join(" 👋 ");
+            let tokens = crate::bpe::encode(text.as_bytes(), lookup);
+            for (size, overlap, slack) in [(8, 0, 0), (8, 3, 2), (16, 4, 8), (5, 4, 4)] {
+                let chunks = crate::bpe::chunk::chunk(&text, lookup, size, overlap, slack).unwrap();
+                assert_eq!(chunks.first().unwrap().range.start, 0);
+                assert_eq!(chunks.last().unwrap().range.end, text.len());
+                for chunk in &chunks {
+                    assert!(chunk.tokens.len() <= size);
+                    assert_eq!(&text[chunk.range.clone()], chunk.text);
+                    assert_eq!(
+                        crate::bpe::decode(&chunk.tokens, &crate::bpe::vocabulary::R50K_UNICODES).unwrap(),
+                        chunk.text.as_bytes()
+                    );
+                }
+                for pair in chunks.windows(2) {
+                    assert!(pair[1].range.start > pair[0].range.start);
+                    assert!(pair[1].range.start <= pair[0].range.end);
+                }
+                if overlap == 0 {
+                    let joined: Vec<u16> = chunks.iter().flat_map(|chunk| chunk.tokens.clone()).collect();
+                    assert_eq!(joined, tokens);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn sizes() {
+        let lookup = &crate::bpe::vocabulary::R50K_TOKENS;
+        assert!(crate::bpe::chunk::chunk("text", lookup, 0, 0, 0).is_err());
+        assert!(crate::bpe::chunk::chunk("text", lookup, 2, 2, 0).is_err());
+
+        // 👋 takes two tokens, so no chunk of one token can hold it.
+        let text = "👋👋👋";
+        assert_eq!(
+            crate::bpe::chunk::chunk(text, lookup, 1, 0, 0),
+            Err("[ERROR]: Chunk size 1 is smaller than the 2 tokens of a single character.".to_string())
+        );
+        for (size, slack) in [(2, 0), (2, 1), (3, 0), (3, 2)] {
+            let chunks = crate::bpe::chunk::chunk(text, lookup, size, 0, slack).unwrap();
+            assert_eq!(chunks.iter().map(|chunk| chunk.text).collect::<String>(), text);
+            assert!(chunks.iter().all(|chunk| chunk.tokens.len() <= size && chunk.text == "👋"));
+        }
+    }
+
+    #[test]
+    fn markdown() {
+        use crate::bpe::chunk::Format;
+        let lookup = &crate::bpe::vocabulary::R50K_TOKENS;
+        let text = "# Title\n\nIntro paragraph.\n\n## Install\n\nRun this:\n\n```sh\n# not a heading\ncargo install gptbpe\n```\n\n## Usage\n\nPipe text in.\n";
+
+        let chunks = crate::bpe::chunk::structured(text, lookup, 1000, Format::Markdown).unwrap();
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].text, text);
+
+        let chunks = crate::bpe::chunk::structured(text, lookup, 20, Format::Markdown).unwrap();
+        assert_eq!(
+            chunks.iter().map(|chunk| chunk.text).collect::<Vec<&str>>(),
+            vec![
+                "# Title\n\nIntro paragraph.\n\n",
+                "## Install\n\nRun this:\n\n",
+                "```sh\n# not a heading\ncargo install gptbpe\n```\n\n",
+                "## Usage\n\nPipe text in.\n",
+            ]
+        );
+    }
+
+    #[test]
+    fn code() {
+        use crate::bpe::chunk::Format;
+        let lookup = &crate::bpe::vocabulary::R50K_TOKENS;
+        let text = "use std::io;\n\n/// Adds.\nfn add(a: u8, b: u8) -> u8 {\n    a + b\n}\n\nfn main() {\n    let x = add(1, 2);\n\n    println!(\"{x}\");\n}\n";
+
+        let chunks = crate::bpe::chunk::structured(text, lookup, 36, Format::Code).unwrap();
+        assert_eq!(
+            chunks.iter().map(|chunk| chunk.text).collect::<Vec<&str>>(),
+            vec![
+                "use std::io;\n\n",
+                "/// Adds.\nfn add(a: u8, b: u8) -> u8 {\n    a + b\n}\n\n",
+                "fn main() {\n    let x = add(1, 2);\n\n    println!(\"{x}\");\n}\n",
+            ]
+        );
+    }
+
+    #[test]
+    fn structured() {
+        use crate::bpe::chunk::Format;
+        let lookup = &crate::bpe::vocabulary::R50K_TOKENS;
+        let mut text = String::new();
+        for (index, words) in helpers::random_text().iter().enumerate() {
+            text.push_str(&format!("## Section {index}\n\n{}\n\n```\n{}\n```\n\n", words.join(" "), words.join("\n")));
+        }
+        for format in [Format::Markdown, Format::Code] {
+            for size in [4, 16, 64] {
+                let chunks = crate::bpe::chunk::structured(&text, lookup, size, format).unwrap();
+                let mut cursor = 0;
+                for chunk in chunks {
+                    assert_eq!(chunk.range.start, cursor);
+                    assert!(chunk.tokens.len() <= size);
+                    assert_eq!(crate::bpe::encode(chunk.text.as_bytes(), lookup), chunk.tokens);
+                    cursor = chunk.range.end;
+                }
+                assert_eq!(cursor, text.len());
+            }
+        }
+
+        assert!(crate::bpe::chunk::structured("# Title\n", lookup, 0, Format::Markdown).is_err());
+        assert!(crate::bpe::chunk::structured("# 👋\n", lookup, 1, Format::Markdown).is_err());
+    }
 }
@@ -1,12 +1,295 @@
 use std::sync::LazyLock;
 use std::collections::BTreeMap;
 
+/// Vocabularies shipped with the encoder.
+///
+/// ## Encoding
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    /// GPT-2 and GPT-3 models.
+    R50K,
+    /// Codex and `text-davinci-002`/`003` models.
+    P50K,
+    /// GPT-3.5 and GPT-4 models.
+    CL100K,
+    /// GPT-4o models.
+    O200K,
+}
+
+impl std::str::FromStr for Encoding {
+    type Err = String;
+
+    fn from_str(encoding: &str) -> Result<Self, Self::Err> {
+        match encoding {
+            "r50k" | "r50k_base" | "gpt2" => Ok(Encoding::R50K),
+            "p50k" | "p50k_base" => Ok(Encoding::P50K),
+            "cl100k" | "cl100k_base" => Ok(Encoding::CL100K),
+            "o200k" | "o200k_base" => Ok(Encoding::O200K),
+            _ => Err(format!("[ERROR]: Unknown encoding '{encoding}', expected one of 'r50k', 'p50k', 'cl100k' or 'o200k'.")),
+        }
+    }
+}
+
+impl Encoding {
+    /// Every encoding, from the oldest to the newest.
+    ///
+    /// ## All
+    pub const ALL: [Encoding; 4] = [Encoding::R50K, Encoding::P50K, Encoding::CL100K, Encoding::O200K];
+
+    /// Name of the encoding, as it parses.
+    ///
+    /// ## Name
+    pub fn name(&self) -> &'static str {
+        match self {
+            Encoding::R50K => "r50k",
+            Encoding::P50K => "p50k",
+            Encoding::CL100K => "cl100k",
+            Encoding::O200K => "o200k",
+        }
+    }
+
+    /// Pretokenizer of the encoding.
+    ///
+    /// ## Pattern
+    pub fn pattern(&self) -> super::Pattern {
+        match self {
+            Encoding::R50K | Encoding::P50K => super::Pattern::Gpt2,
+            Encoding::CL100K => super::Pattern::Cl100k,
+            Encoding::O200K => super::Pattern::O200k,
+        }
+    }
+
+    /// Encoding a lookup table belongs to, found by its address so the table does not load.
+    ///
+    /// ## Of
+    /// ### Arguments
+    /// * `lookup` - a lookup table with vocabulary scheme (slice to tokens).
+    ///
+    /// ### Returns
+    /// * the encoding of one of the shipped tables, `None` for any other table.
+    pub fn of<D>(lookup: &LazyLock<BTreeMap<Vec<u8>, D>>) -> Option<Encoding> {
+        let lookup = lookup as *const LazyLock<BTreeMap<Vec<u8>, D>>;
+        match () {
+            _ if std::ptr::addr_eq(lookup, &R50K_TOKENS) => Some(Encoding::R50K),
+            _ if std::ptr::addr_eq(lookup, &P50K_TOKENS) => Some(Encoding::P50K),
+            _ if std::ptr::addr_eq(lookup, &CL100K_TOKENS) => Some(Encoding::CL100K),
+            _ if std::ptr::addr_eq(lookup, &O200K_TOKENS) => Some(Encoding::O200K),
+            _ => None,
+        }
+    }
+
+    /// Whether the vocabulary file of the encoding exists, so its tables can load.
+    ///
+    /// ## Available
+    pub fn available(&self) -> bool {
+        std::path::Path::new(self.path()).exists()
+    }
+
+    /// Counts the tokens of a byte slice with the vocabulary of the encoding.
+    /// See [count tokens](super::count_tokens) for implementation.
+    ///
+    /// ## Count
+    /// ### Arguments
+    /// * `slice` - a byte vector.
+    ///
+    /// ### Returns
+    /// * number of tokens.
+    pub fn count(&self, slice: &[u8]) -> usize {
+        match self {
+            Encoding::R50K => super::count_tokens(slice, &R50K_TOKENS),
+            Encoding::P50K => super::count_tokens(slice, &P50K_TOKENS),
+            Encoding::CL100K => super::count_tokens(slice, &CL100K_TOKENS),
+            Encoding::O200K => super::count_tokens(slice, &O200K_TOKENS),
+        }
+    }
+
+    /// Encodes a byte slice with the vocabulary of the encoding.
+    /// See [encode](super::encode) for implementation.
+    ///
+    /// ## Encode
+    /// ### Arguments
+    /// * `slice` - a byte vector.
+    ///
+    /// ### Returns
+    /// * a token vector, ids widened to `u32`.
+    pub fn encode(&self, slice: &[u8]) -> Vec<u32> {
+        match self {
+            Encoding::R50K => super::encode(slice, &R50K_TOKENS).into_iter().map(u32::from).collect(),
+            Encoding::P50K => super::encode(slice, &P50K_TOKENS).into_iter().map(u32::from).collect(),
+            Encoding::CL100K => super::encode(slice, &CL100K_TOKENS),
+            Encoding::O200K => super::encode(slice, &O200K_TOKENS),
+        }
+    }
+
+    /// Decodes token ids with the vocabulary of the encoding.
+    /// See [decode](super::decode) for implementation.
+    ///
+    /// ## Decode
+    /// ### Arguments
+    /// * `ids` - token ids.
+    ///
+    /// ### Returns
+    /// * the decoded bytes, or the first id that is not in the vocabulary.
+    pub fn decode(&self, ids: &[u32]) -> Result<Vec<u8>, u32> {
+        fn narrow<D: TryFrom<u32> + Ord + Clone>(ids: &[u32], unicodes: &LazyLock<BTreeMap<D, Vec<u8>>>) -> Result<Vec<u8>, u32>
+        where
+            u32: From<D>,
+        {
+            let tokens = ids
+                .iter()
+                .map(|id| D::try_from(*id).map_err(|_| *id))
+                .collect::<Result<Vec<D>, u32>>()?;
+            super::decode(&tokens, unicodes).map_err(u32::from)
+        }
+        match self {
+            Encoding::R50K => narrow(ids, &R50K_UNICODES),
+            Encoding::P50K => narrow(ids, &P50K_UNICODES),
+            Encoding::CL100K => narrow(ids, &CL100K_UNICODES),
+            Encoding::O200K => narrow(ids, &O200K_UNICODES),
+        }
+    }
+
+    /// Truncates a text to at most `max_tokens` tokens with the vocabulary of the encoding.
+    /// See [truncate](super::truncate) for implementation.
+    ///
+    /// ## Truncate
+    /// ### Arguments
+    /// * `text` - a text.
+    /// * `max_tokens` - the maximum number of tokens to keep.
+    /// * `side` - the side tokens are cut from.
+    ///
+    /// ### Returns
+    /// * the truncated text and the kept tokens, ids widened to `u32`.
+    pub fn truncate<'a>(&self, text: &'a str, max_tokens: usize, side: super::Side) -> (&'a str, Vec<u32>) {
+        fn widen<D: Into<u32>>((text, tokens): (&str, Vec<D>)) -> (&str, Vec<u32>) {
+            (text, tokens.into_iter().map(Into::into).collect())
+        }
+        match self {
+            Encoding::R50K => widen(super::truncate(text, &R50K_TOKENS, max_tokens, side)),
+            Encoding::P50K => widen(super::truncate(text, &P50K_TOKENS, max_tokens, side)),
+            Encoding::CL100K => widen(super::truncate(text, &CL100K_TOKENS, max_tokens, side)),
+            Encoding::O200K => widen(super::truncate(text, &O200K_TOKENS, max_tokens, side)),
+        }
+    }
+
+    /// Splits a text into windows of at most `size` tokens with the vocabulary of the encoding.
+    /// See [chunk](super::chunk::chunk) for implementation.
+    ///
+    /// ## Chunk
+    /// ### Arguments
+    /// * `text` - a text.
+    /// * `size` - the maximum number of tokens per chunk.
+    /// * `overlap` - the number of tokens repeated at the start of the next chunk, less than `size`.
+    /// * `slack` - the number of tokens a chunk may give up to end on a better boundary.
+    ///
+    /// ### Returns
+    /// * the chunks, in order, ids widened to `u32`, or an error for sizes [chunk](super::chunk::chunk) rejects.
+    pub fn chunk<'a>(&self, text: &'a str, size: usize, overlap: usize, slack: usize) -> Result<Vec<super::chunk::Chunk<'a, u32>>, String> {
+        match self {
+            Encoding::R50K => super::chunk::chunk(text, &R50K_TOKENS, size, overlap, slack).map(widen),
+            Encoding::P50K => super::chunk::chunk(text, &P50K_TOKENS, size, overlap, slack).map(widen),
+            Encoding::CL100K => super::chunk::chunk(text, &CL100K_TOKENS, size, overlap, slack).map(widen),
+            Encoding::O200K => super::chunk::chunk(text, &O200K_TOKENS, size, overlap, slack).map(widen),
+        }
+    }
+
+    /// Splits a text along its structure into chunks of at most `size` tokens with the vocabulary of the encoding.
+    /// See [structured](super::chunk::structured) for implementation.
+    ///
+    /// ## Structured
+    /// ### Arguments
+    /// * `text` - a text.
+    /// * `size` - the maximum number of tokens per chunk.
+    /// * `format` - the [structure](super::chunk::Format) to look for.
+    ///
+    /// ### Returns
+    /// * the chunks, in order, ids widened to `u32`, or an error for sizes [structured](super::chunk::structured) rejects.
+    pub fn structured<'a>(&self, text: &'a str, size: usize, format: super::chunk::Format) -> Result<Vec<super::chunk::Chunk<'a, u32>>, String> {
+        match self {
+            Encoding::R50K => super::chunk::structured(text, &R50K_TOKENS, size, format).map(widen),
+            Encoding::P50K => super::chunk::structured(text, &P50K_TOKENS, size, format).map(widen),
+            Encoding::CL100K => super::chunk::structured(text, &CL100K_TOKENS, size, format).map(widen),
+            Encoding::O200K => super::chunk::structured(text, &O200K_TOKENS, size, format).map(widen),
+        }
+    }
+
+    /// Encodes a byte slice with the vocabulary of the encoding, with the byte offsets of every token.
+    /// See [encode with offsets](super::encode_with_offsets) for implementation.
+    ///
+    /// ## Offsets
+    /// ### Arguments
+    /// * `slice` - a byte vector.
+    ///
+    /// ### Returns
+    /// * `(token, start, end)` for every token, ids widened to `u32`.
+    pub fn offsets(&self, slice: &[u8]) -> Vec<(u32, usize, usize)> {
+        fn widen<D: Into<u32>>(offsets: Vec<(D, usize, usize)>) -> Vec<(u32, usize, usize)> {
+            offsets.into_iter().map(|(token, start, end)| (token.into(), start, end)).collect()
+        }
+        match self {
+            Encoding::R50K => widen(super::encode_with_offsets(slice, &R50K_TOKENS)),
+            Encoding::P50K => widen(super::encode_with_offsets(slice, &P50K_TOKENS)),
+            Encoding::CL100K => widen(super::encode_with_offsets(slice, &CL100K_TOKENS)),
+            Encoding::O200K => widen(super::encode_with_offsets(slice, &O200K_TOKENS)),
+        }
+    }
+
+    /// Path of the vocabulary file, relative to the crate root.
+    ///
+    /// ## Path
+    pub fn path(&self) -> &'static str {
+        match self {
+            Encoding::R50K => "src/bpe/vocabulary/r50k.jsonl",
+            Encoding::P50K => "src/bpe/vocabulary/p50k.jsonl",
+            Encoding::CL100K => "src/bpe/vocabulary/cl100k.jsonl",
+            Encoding::O200K => "src/bpe/vocabulary/o200k.jsonl",
+        }
+    }
+}
+
+/// Widens the token ids of chunks to `u32`.
+fn widen<D: Into<u32>>(chunks: Vec<super::chunk::Chunk<'_, D>>) -> Vec<super::chunk::Chunk<'_, u32>> {
+    chunks
+        .into_iter()
+        .map(|chunk| super::chunk::Chunk { text: chunk.text, tokens: chunk.tokens.into_iter().map(Into::into).collect(), range: chunk.range })
+        .collect()
+}
+
+/// Reads a vocabulary key into the [GPT unicode](super::GPT_UNICODES) scheme.
+///
+/// Tokens that are not valid UTF-8 on their own are stored as comma separated decimal bytes (`"32,240,159"`),
+/// and single byte tokens above `127` as their decimal byte (`"240"`).
+/// Every other key is already in the GPT unicode scheme.
+///
+/// ## Unicodes
+/// ### Arguments
+/// * `key` - vocabulary key
+/// * `byte` - whether the key belongs to one of the 256 single byte tokens
+///
+/// ### Returns
+/// * GPT unicode bytes.
+fn unicodes(key: String, byte: bool) -> Vec<u8> {
+    let is_decimal = key.contains(',') || (byte && key.len() > 1);
+    let bytes: Option<Vec<u8>> = match is_decimal {
+        true => key.split(',').map(|b| b.parse::<u8>().ok()).collect(),
+        false => None,
+    };
+    match bytes {
+        Some(bytes) => {
+            let mut buffer = vec![];
+            super::to_unicodes(&bytes, &mut buffer);
+            buffer
+        }
+        None => key.into_bytes(),
+    }
+}
+
 /// Maps R50K vocabulary tokens from GPT unicode scheme.
 ///
 /// ## R50K tokens
-pub (crate) static R50K_TOKENS: LazyLock<BTreeMap<Vec<u8>, u16>> = LazyLock::new(|| {
+pub static R50K_TOKENS: LazyLock<BTreeMap<Vec<u8>, u16>> = LazyLock::new(|| {
     let mut encoder = std::collections::BTreeMap::new();
-    let file = std::fs::File::open("src/bpe/vocabulary/r50k.jsonl")
+    let file = std::fs::File::open(Encoding::R50K.path())
         .expect("[ERROR]: Could not load r50k tokens");
     let file = std::io::BufReader::new(file);
 
@@ -15,7 +298,7 @@ pub (crate) static R50K_TOKENS: LazyLock<BTreeMap<Vec<u8>, u16>> = LazyLock::new
         let mut data: BTreeMap<String, u16> =
             serde_json::from_str(_line.as_str()).expect("[ERROR]: Could not load r50k tokens");
         while let Some((key, value)) = data.pop_first() {
-            encoder.insert(key.into_bytes(), value);
+            encoder.insert(unicodes(key, value < 256), value);
         }
     };
 
@@ -25,7 +308,7 @@ pub (crate) static R50K_TOKENS: LazyLock<BTreeMap<Vec<u8>, u16>> = LazyLock::new
 /// GPT unicode scheme from R50K tokens.
 ///
 /// ## R50K unicodes
-pub (crate) static R50K_UNICODES: LazyLock<BTreeMap<u16, Vec<u8>>> = LazyLock::new(|| {
+pub static R50K_UNICODES: LazyLock<BTreeMap<u16, Vec<u8>>> = LazyLock::new(|| {
     let mut decode = std::collections::BTreeMap::new();
     for (key, value) in R50K_TOKENS.iter() {
         decode.insert(*value, key.to_vec());
@@ -36,10 +319,10 @@ pub (crate) static R50K_UNICODES: LazyLock<BTreeMap<u16, Vec<u8>>> = LazyLock::n
 /// Maps P50K vocabulary tokens from GPT unicode scheme.
 ///
 /// ## P50K tokens
-pub (crate) static P50K_TOKENS: LazyLock<BTreeMap<Vec<u8>, u16>> = LazyLock::new(|| {
+pub static P50K_TOKENS: LazyLock<BTreeMap<Vec<u8>, u16>> = LazyLock::new(|| {
     let mut encoder = std::collections::BTreeMap::new();
-    let file = std::fs::File::open("src/bpe/vocabulary/p50k.jsonl")
-        .expect("[ERROR]: Could not load r50k tokens");
+    let file = std::fs::File::open(Encoding::P50K.path())
+        .expect("[ERROR]: Could not load p50k tokens");
     let file = std::io::BufReader::new(file);
 
     for line in std::io::BufRead::lines(file) {
@@ -47,18 +330,18 @@ pub (crate) static P50K_TOKENS: LazyLock<BTreeMap<Vec<u8>, u16>> = LazyLock::new
         let mut data: BTreeMap<String, u16> =
             serde_json::from_str(_line.as_str()).expect("[ERROR]: Could not load p50k tokens");
         while let Some((key, value)) = data.pop_first() {
-            encoder.insert(key.into_bytes(), value);
+            encoder.insert(unicodes(key, value < 256), value);
         }
     }
     encoder
 });
 
-/// GPT unicode scheme from R50K tokens.
+/// GPT unicode scheme from P50K tokens.
 ///
 /// ## P50K unicodes
-pub (crate) static P50K_UNICODES: LazyLock<BTreeMap<u16, Vec<u8>>> = LazyLock::new(|| {
+pub static P50K_UNICODES: LazyLock<BTreeMap<u16, Vec<u8>>> = LazyLock::new(|| {
     let mut decode = std::collections::BTreeMap::new();
-    for (key, value) in R50K_TOKENS.iter() {
+    for (key, value) in P50K_TOKENS.iter() {
         decode.insert(*value, key.to_vec());
     };
     decode
@@ -67,18 +350,18 @@ pub (crate) static P50K_UNICODES: LazyLock<BTreeMap<u16, Vec<u8>>> = LazyLock::n
 /// Maps CL100K vocabulary tokens from GPT unicode scheme.
 ///
 /// ## CL100K tokens
-pub (crate) static CL100K_TOKENS: LazyLock<BTreeMap<Vec<u8>, u16>> = LazyLock::new(|| {
+pub static CL100K_TOKENS: LazyLock<BTreeMap<Vec<u8>, u32>> = LazyLock::new(|| {
     let mut encoder = std::collections::BTreeMap::new();
-    let file = std::fs::File::open("src/bpe/vocabulary/cl100k.jsonl")
+    let file = std::fs::File::open(Encoding::CL100K.path())
         .expect("[ERROR]: Could not load cl100k tokens");
     let file = std::io::BufReader::new(file);
 
     for line in std::io::BufRead::lines(file) {
         let _line = line.unwrap();
-        let mut data: BTreeMap<String, u16> =
+        let mut data: BTreeMap<String, u32> =
             serde_json::from_str(_line.as_str()).expect("[ERROR]: Could not load cl100k tokens");
         while let Some((key, value)) = data.pop_first() {
-            encoder.insert(key.into_bytes(), value);
+            encoder.insert(unicodes(key, value < 256), value);
         }
     }
     encoder
@@ -87,7 +370,7 @@ pub (crate) static CL100K_TOKENS: LazyLock<BTreeMap<Vec<u8>, u16>> = LazyLock::n
 /// GPT unicode scheme from CL100K tokens.
 ///
 /// ## CL100K unicodes
-pub (crate) static CL100K_UNICODES: LazyLock<BTreeMap<u16, Vec<u8>>> = LazyLock::new(|| {
+pub static CL100K_UNICODES: LazyLock<BTreeMap<u32, Vec<u8>>> = LazyLock::new(|| {
     let mut decode = std::collections::BTreeMap::new();
     for (key, value) in CL100K_TOKENS.iter() {
         decode.insert(*value, key.to_vec());
@@ -98,9 +381,9 @@ pub (crate) static CL100K_UNICODES: LazyLock<BTreeMap<u16, Vec<u8>>> = LazyLock:
 /// Maps O200K vocabulary tokens from GPT unicode scheme.
 ///
 /// ## O200K tokens
-pub (crate) static O200K_TOKENS: LazyLock<BTreeMap<Vec<u8>, u32>> = LazyLock::new(|| {
+pub static O200K_TOKENS: LazyLock<BTreeMap<Vec<u8>, u32>> = LazyLock::new(|| {
     let mut encoder = std::collections::BTreeMap::new();
-    let file = std::fs::File::open("src/bpe/vocabulary/o200k.jsonl")
+    let file = std::fs::File::open(Encoding::O200K.path())
         .expect("[ERROR]: Could not load o200k tokens");
     let file = std::io::BufReader::new(file);
 
@@ -109,7 +392,7 @@ pub (crate) static O200K_TOKENS: LazyLock<BTreeMap<Vec<u8>, u32>> = LazyLock::ne
         let mut data: BTreeMap<String, u32> =
             serde_json::from_str(_line.as_str()).expect("[ERROR]: Could not load o200k tokens");
         while let Some((key, value)) = data.pop_first() {
-            encoder.insert(key.into_bytes(), value);
+            encoder.insert(unicodes(key, value < 256), value);
         }
     }
     encoder
@@ -118,10 +401,49 @@ pub (crate) static O200K_TOKENS: LazyLock<BTreeMap<Vec<u8>, u32>> = LazyLock::ne
 /// GPT unicode scheme from O200K tokens.
 ///
 /// ## O200K unicodes
-pub (crate) static O200K_UNICODES: LazyLock<BTreeMap<u32, Vec<u8>>> = LazyLock::new(|| {
+pub static O200K_UNICODES: LazyLock<BTreeMap<u32, Vec<u8>>> = LazyLock::new(|| {
     let mut decode = std::collections::BTreeMap::new();
     for (key, value) in O200K_TOKENS.iter() {
         decode.insert(*value, key.to_vec());
     };
     decode
-});
\ No newline at end of file
+});
+
+#[cfg(test)]
+mod tests {
+    use crate::bpe::vocabulary::Encoding;
+
+    #[test]
+    fn encodings() {
+        assert_eq!("gpt2".parse::<Encoding>(), Ok(Encoding::R50K));
+        assert_eq!("cl100k_base".parse::<Encoding>(), Ok(Encoding::CL100K));
+        assert!("cl200k".parse::<Encoding>().is_err());
+        assert_eq!(Encoding::CL100K.offsets(" mouths".as_bytes()), vec![(65609, 0, 7)]);
+
+        // P50K only tokens decode with the P50K unicodes.
+        let tokens = crate::bpe::encode("x\n                       y".as_bytes(), &crate::bpe::vocabulary::P50K_TOKENS);
+        assert!(tokens.iter().any(|token| *token > 50256));
+        assert_eq!(crate::bpe::decode(&tokens, &crate::bpe::vocabulary::P50K_UNICODES).unwrap(), "x\n                       y".as_bytes());
+
+        // CL100K ids do not fit in 16 bits.
+        let tokens = crate::bpe::encode("hello world 👋".as_bytes(), &crate::bpe::vocabulary::CL100K_TOKENS);
+        assert_eq!(tokens, vec![15339, 1917, 62904, 233]);
+        let tokens = crate::bpe::encode(" mouths".as_bytes(), &crate::bpe::vocabulary::CL100K_TOKENS);
+        assert!(tokens.iter().all(|token| *token > u16::MAX as u32));
+        assert_eq!(crate::bpe::decode(&tokens, &crate::bpe::vocabulary::CL100K_UNICODES).unwrap(), " mouths".as_bytes());
+
+        // R50K splits contractions and leaves the last space of a run to the next word, as GPT-2 does.
+        let encode = |text: &str| crate::bpe::encode(text.as_bytes(), &crate::bpe::vocabulary::R50K_TOKENS);
+        assert_eq!(encode("it's"), vec![270, 338]);
+        assert_eq!(encode("I'll go"), vec![40, 1183, 467]);
+        assert_eq!(encode("  hello"), vec![220, 23748]);
+        assert_eq!(encode("a\n\nb"), vec![64, 198, 198, 65]);
+
+        // CL100K pretokenizes digits by three, contractions in any case and whitespace before words apart.
+        let encode = |text: &str| crate::bpe::encode(text.as_bytes(), &crate::bpe::vocabulary::CL100K_TOKENS);
+        assert_eq!(encode("1234567"), vec![4513, 10961, 22]);
+        assert_eq!(encode("a  b"), vec![64, 220, 293]);
+        assert_eq!(encode("  x\n\n y"), vec![220, 865, 271, 379]);
+        assert_eq!(encode("you'll"), vec![9514, 3358]);
+    }
+}
@@ -0,0 +1,79 @@
+use gptbpe::bpe;
+use gptbpe::bpe::vocabulary::Encoding;
+use std::fmt::Write;
+
+/// Renders the comparison of a text across encodings.
+///
+/// A table gives the tokens, bytes per token and token difference against the first encoding.
+/// Unless `summary`, every line of the text follows, once per encoding, with its tokens separated by `|`.
+///
+/// ## Render
+/// ### Arguments
+/// * `data` - the compared bytes.
+/// * `encodings` - the encodings to compare, all available.
+/// * `summary` - whether to leave the token boundaries out.
+///
+/// ### Returns
+/// * the rendered comparison.
+pub fn render(data: &[u8], encodings: &[Encoding], summary: bool) -> String {
+    let mut output = String::new();
+    let statistics = bpe::compare::compare(&[data], encodings);
+    writeln!(output, "{:<8} {:>10} {:>12} {:>8}", "encoding", "tokens", "bytes/token", "delta").unwrap();
+    for row in &statistics {
+        let delta = match statistics.first() {
+            Some(first) if first.tokens > 0 => {
+                format!("{:+.1}%", (row.tokens as f64 - first.tokens as f64) * 100.0 / first.tokens as f64)
+            }
+            _ => "-".to_string(),
+        };
+        writeln!(output, "{:<8} {:>10} {:>12.2} {:>8}", row.encoding.name(), row.tokens, row.bytes_per_token(), delta).unwrap();
+    }
+    if summary || encodings.is_empty() {
+        return output;
+    };
+
+    // Separators never add line breaks, so the rendered lines of every encoding line up with the text.
+    let rendered: Vec<Vec<String>> = encodings
+        .iter()
+        .map(|encoding| {
+            crate::show::render(data, &encoding.offsets(data), false, false)
+                .lines()
+                .map(str::to_string)
+                .collect()
+        })
+        .collect();
+    let lines = rendered.iter().map(Vec::len).max().unwrap_or(0);
+    for line in 0..lines {
+        writeln!(output).unwrap();
+        for (encoding, rendered) in encodings.iter().zip(&rendered) {
+            let text = rendered.get(line).map(String::as_str).unwrap_or("");
+            writeln!(output, "{:<8} {text}", encoding.name()).unwrap();
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use gptbpe::bpe::vocabulary::Encoding;
+
+    #[test]
+    fn render() {
+        let encodings = [Encoding::R50K, Encoding::CL100K];
+        let table = "encoding     tokens  bytes/token    delta\n\
+                     r50k              4         4.00    +0.0%\n\
+                     cl100k            5         3.20   +25.0%\n";
+        assert_eq!(super::render(b"hello world 1234", &encodings, true), table);
+        assert_eq!(
+            super::render(b"hello world 1234", &encodings, false),
+            format!("{table}\nr50k     hello| world| 12|34\ncl100k   hello| world| |123|4\n")
+        );
+    }
+
+    #[test]
+    fn empty() {
+        let output = super::render(b"", &[Encoding::R50K], true);
+        assert_eq!(output.lines().nth(1), Some("r50k              0         0.00        -"));
+        assert_eq!(super::render(b"text", &[], false), "encoding     tokens  bytes/token    delta\n");
+    }
+}
@@ -0,0 +1,306 @@
+//! Unix domain socket daemon with a compact, length-prefixed binary protocol, and its client.
+//!
+//! Every integer is little-endian. A request is `u32 length`, then `length` bytes:
+//! `u8 encoding` (`0` r50k, `1` p50k, `2` cl100k, `3` o200k), `u8 operation` and the payload.
+//!
+//! | operation    | request payload                                          | response payload     |
+//! |--------------|----------------------------------------------------------|----------------------|
+//! | `0` encode   | text bytes                                               | `u32` ids            |
+//! | `1` decode   | `u32` ids                                                | bytes                |
+//! | `2` count    | text bytes                                               | `u32` count          |
+//! | `3` truncate | `u32` max tokens, `u8` side (`0` end, `1` start), text   | truncated text bytes |
+//!
+//! A response is `u32 length`, then `length` bytes: `u8 status` (`0` ok, `1` error) and the payload,
+//! a UTF-8 message for errors. Connections stay open for any number of requests.
+use crate::bpe;
+use crate::bpe::vocabulary::Encoding;
+use std::io::Read;
+use std::io::Write;
+use std::os::unix::net::UnixListener;
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+
+/// Largest frame accepted, in bytes.
+///
+/// ## Maximum frame
+const MAXIMUM_FRAME: usize = 64 * 1024 * 1024;
+
+const ENCODE: u8 = 0;
+const DECODE: u8 = 1;
+const COUNT: u8 = 2;
+const TRUNCATE: u8 = 3;
+
+const OK: u8 = 0;
+const ERROR: u8 = 1;
+
+/// Wire identifier of an encoding.
+fn identifier(encoding: Encoding) -> u8 {
+    match encoding {
+        Encoding::R50K => 0,
+        Encoding::P50K => 1,
+        Encoding::CL100K => 2,
+        Encoding::O200K => 3,
+    }
+}
+
+/// Reads a length-prefixed frame, `None` when the connection closed between frames.
+fn read_frame(stream: &mut impl Read) -> std::io::Result<Option<Vec<u8>>> {
+    let mut length = [0; 4];
+    match stream.read_exact(&mut length) {
+        Ok(()) => {}
+        Err(error) if error.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(error) => return Err(error),
+    };
+    let length = u32::from_le_bytes(length) as usize;
+    if length > MAXIMUM_FRAME {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "[ERROR]: Frame too large."));
+    };
+    let mut frame = vec![];
+    if stream.take(length as u64).read_to_end(&mut frame)? < length {
+        return Err(std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "[ERROR]: Frame ended early."));
+    };
+    Ok(Some(frame))
+}
+
+/// Writes a length-prefixed frame made of a header and a payload.
+fn write_frame(stream: &mut impl Write, header: &[u8], payload: &[u8]) -> std::io::Result<()> {
+    let length = u32::try_from(header.len() + payload.len())
+        .map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidInput, "[ERROR]: Frame too large."))?;
+    let mut frame = Vec::with_capacity(4 + length as usize);
+    frame.extend_from_slice(&length.to_le_bytes());
+    frame.extend_from_slice(header);
+    frame.extend_from_slice(payload);
+    stream.write_all(&frame)?;
+    stream.flush()
+}
+
+/// Packs ids as little-endian `u32`.
+fn pack(ids: &[u32]) -> Vec<u8> {
+    ids.iter().flat_map(|id| id.to_le_bytes()).collect()
+}
+
+/// Unpacks little-endian `u32` ids.
+fn unpack(bytes: &[u8]) -> Result<Vec<u32>, String> {
+    if !bytes.len().is_multiple_of(4) {
+        return Err("[ERROR]: Ids payload is not a multiple of 4 bytes.".to_string());
+    };
+    Ok(bytes.chunks_exact(4).map(|id| u32::from_le_bytes([id[0], id[1], id[2], id[3]])).collect())
+}
+
+/// Answers a request frame.
+///
+/// ## Answer
+/// ### Returns
+/// * the response payload, or an error message.
+fn answer(frame: &[u8]) -> Result<Vec<u8>, String> {
+    let [encoding, operation, payload @ ..] = frame else {
+        return Err("[ERROR]: Request is missing its encoding or operation.".to_string());
+    };
+    let encoding = match Encoding::ALL.into_iter().find(|candidate| identifier(*candidate) == *encoding) {
+        Some(encoding) if encoding.available() => encoding,
+        Some(encoding) => return Err(format!("[ERROR]: Vocabulary file '{}' not found.", encoding.path())),
+        None => return Err(format!("[ERROR]: Unknown encoding {encoding}.")),
+    };
+    match *operation {
+        ENCODE => Ok(pack(&encoding.encode(payload))),
+        DECODE => encoding
+            .decode(&unpack(payload)?)
+            .map_err(|id| format!("[ERROR]: Token id {id} is not in the vocabulary.")),
+        COUNT => {
+            let count = u32::try_from(encoding.count(payload)).unwrap_or(u32::MAX);
+            Ok(count.to_le_bytes().to_vec())
+        }
+        TRUNCATE => {
+            let [a, b, c, d, side, text @ ..] = payload else {
+                return Err("[ERROR]: Truncate payload is missing its maximum tokens or side.".to_string());
+            };
+            let side = match side {
+                0 => bpe::Side::End,
+                1 => bpe::Side::Start,
+                _ => return Err(format!("[ERROR]: Unknown side {side}.")),
+            };
+            let text = std::str::from_utf8(text).map_err(|error| format!("[ERROR]: Text is not UTF-8: {error}"))?;
+            let max_tokens = u32::from_le_bytes([*a, *b, *c, *d]) as usize;
+            Ok(encoding.truncate(text, max_tokens, side).0.as_bytes().to_vec())
+        }
+        _ => Err(format!("[ERROR]: Unknown operation {operation}.")),
+    }
+}
+
+/// Answers the requests of a connection until the client closes it.
+///
+/// ## Connection
+fn connection(mut stream: UnixStream) -> std::io::Result<()> {
+    while let Some(frame) = read_frame(&mut stream)? {
+        match answer(&frame) {
+            Ok(payload) => write_frame(&mut stream, &[OK], &payload)?,
+            Err(message) => write_frame(&mut stream, &[ERROR], message.as_bytes())?,
+        };
+    }
+    Ok(())
+}
+
+/// Serves the binary protocol from a listener, one thread per connection, until accepting fails.
+///
+/// ## Serve
+/// ### Arguments
+/// * `listener` - a bound Unix domain socket listener.
+///
+/// ### Returns
+/// * the accept error that stopped the daemon.
+pub fn serve(listener: UnixListener) -> std::io::Result<()> {
+    for stream in listener.incoming() {
+        let stream = stream?;
+        std::thread::spawn(move || {
+            if let Err(error) = connection(stream) {
+                eprintln!("[ERROR]: {error}");
+            };
+        });
+    }
+    Ok(())
+}
+
+/// Client of the [daemon](serve), keeping one connection open across requests.
+///
+/// ## Client
+pub struct Client {
+    ///
+    /// ## Stream
+    stream: UnixStream,
+}
+
+impl Client {
+    /// Connects to a daemon socket.
+    ///
+    /// ## Connect
+    /// ### Arguments
+    /// * `path` - path of the socket.
+    ///
+    /// ### Returns
+    /// * a client, or the connection error.
+    pub fn connect(path: impl AsRef<Path>) -> std::io::Result<Client> {
+        Ok(Client { stream: UnixStream::connect(path)? })
+    }
+
+    /// Sends a request and reads its response payload, daemon errors becoming [other](std::io::ErrorKind::Other) errors.
+    ///
+    /// ## Request
+    fn request(&mut self, encoding: Encoding, operation: u8, payload: &[u8]) -> std::io::Result<Vec<u8>> {
+        write_frame(&mut self.stream, &[identifier(encoding), operation], payload)?;
+        let frame = read_frame(&mut self.stream)?
+            .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "[ERROR]: Daemon closed the connection."))?;
+        match frame.split_first() {
+            Some((&OK, payload)) => Ok(payload.to_vec()),
+            Some((_, message)) => Err(std::io::Error::other(String::from_utf8_lossy(message).to_string())),
+            None => Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "[ERROR]: Empty response.")),
+        }
+    }
+
+    /// Encodes a byte slice.
+    ///
+    /// ## Encode
+    /// ### Arguments
+    /// * `encoding` - the vocabulary.
+    /// * `slice` - a byte vector.
+    ///
+    /// ### Returns
+    /// * a token vector.
+    pub fn encode(&mut self, encoding: Encoding, slice: &[u8]) -> std::io::Result<Vec<u32>> {
+        let payload = self.request(encoding, ENCODE, slice)?;
+        unpack(&payload).map_err(|message| std::io::Error::new(std::io::ErrorKind::InvalidData, message))
+    }
+
+    /// Decodes token ids.
+    ///
+    /// ## Decode
+    /// ### Arguments
+    /// * `encoding` - the vocabulary.
+    /// * `ids` - token ids.
+    ///
+    /// ### Returns
+    /// * the decoded bytes.
+    pub fn decode(&mut self, encoding: Encoding, ids: &[u32]) -> std::io::Result<Vec<u8>> {
+        self.request(encoding, DECODE, &pack(ids))
+    }
+
+    /// Counts the tokens of a byte slice.
+    ///
+    /// ## Count
+    /// ### Arguments
+    /// * `encoding` - the vocabulary.
+    /// * `slice` - a byte vector.
+    ///
+    /// ### Returns
+    /// * number of tokens.
+    pub fn count(&mut self, encoding: Encoding, slice: &[u8]) -> std::io::Result<usize> {
+        let payload = self.request(encoding, COUNT, slice)?;
+        match <[u8; 4]>::try_from(payload.as_slice()) {
+            Ok(count) => Ok(u32::from_le_bytes(count) as usize),
+            Err(_) => Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "[ERROR]: Count payload is not 4 bytes.")),
+        }
+    }
+
+    /// Truncates a text to at most `max_tokens` tokens.
+    ///
+    /// ## Truncate
+    /// ### Arguments
+    /// * `encoding` - the vocabulary.
+    /// * `text` - a text.
+    /// * `max_tokens` - the maximum number of tokens to keep.
+    /// * `side` - the side tokens are cut from.
+    ///
+    /// ### Returns
+    /// * the truncated text.
+    pub fn truncate(&mut self, encoding: Encoding, text: &str, max_tokens: u32, side: bpe::Side) -> std::io::Result<String> {
+        let mut payload = max_tokens.to_le_bytes().to_vec();
+        payload.push(match side {
+            bpe::Side::End => 0,
+            bpe::Side::Start => 1,
+        });
+        payload.extend_from_slice(text.as_bytes());
+        let text = self.request(encoding, TRUNCATE, &payload)?;
+        String::from_utf8(text).map_err(|error| std::io::Error::new(std::io::ErrorKind::InvalidData, error))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bpe;
+    use crate::bpe::vocabulary::Encoding;
+    use super::Client;
+
+    /// Starts a daemon on a fresh socket in the temporary directory.
+    fn start(name: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("gptbpe-{}-{name}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let listener = std::os::unix::net::UnixListener::bind(&path).unwrap();
+        std::thread::spawn(move || super::serve(listener));
+        path
+    }
+
+    #[test]
+    fn loopback() {
+        let path = start("loopback");
+        let mut client = Client::connect(&path).unwrap();
+
+        assert_eq!(client.encode(Encoding::CL100K, "hello world 👋".as_bytes()).unwrap(), [15339, 1917, 62904, 233]);
+        assert_eq!(client.decode(Encoding::CL100K, &[15339, 1917, 62904, 233]).unwrap(), "hello world 👋".as_bytes());
+        assert_eq!(client.count(Encoding::R50K, b"hello world").unwrap(), 2);
+        assert_eq!(client.truncate(Encoding::R50K, "hello big world", 2, bpe::Side::Start).unwrap(), " big world");
+
+        let error = client.decode(Encoding::R50K, &[99999]).unwrap_err();
+        assert_eq!(error.to_string(), "[ERROR]: Token id 99999 is not in the vocabulary.");
+        assert_eq!(client.count(Encoding::R50K, b"").unwrap(), 0);
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn short_frame() {
+        let mut frame = (super::MAXIMUM_FRAME as u32).to_le_bytes().to_vec();
+        frame.extend_from_slice(b"abc");
+        let error = super::read_frame(&mut frame.as_slice()).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::UnexpectedEof);
+        assert!(super::read_frame(&mut [].as_slice()).unwrap().is_none());
+    }
+}
@@ -0,0 +1,135 @@
+use gptbpe::bpe::tree::Tree;
+use std::fmt::Display;
+use std::fmt::Write;
+use std::ops::Range;
+
+/// Style of the HTML page, drawing nested lists as top-down trees.
+///
+/// ## Style
+const STYLE: &str = "
+body { font-family: ui-monospace, monospace; margin: 2em; }
+section { margin-bottom: 2em; overflow-x: auto; }
+h2 { font-size: 1em; font-weight: normal; color: #555; }
+ul { display: flex; justify-content: center; position: relative; margin: 0; padding: 1em 0 0; }
+li { list-style: none; position: relative; text-align: center; padding: 1em .25em 0; }
+li::before, li::after { content: ''; position: absolute; top: 0; right: 50%; width: 50%; height: 1em; border-top: 1px solid #999; }
+li::after { right: auto; left: 50%; border-left: 1px solid #999; }
+li:first-child::before, li:last-child::after { border: 0 none; }
+li:last-child::before { border-right: 1px solid #999; }
+li:only-child::before, li:only-child::after { display: none; }
+li:only-child { padding-top: 0; }
+ul ul::before { content: ''; position: absolute; top: 0; left: 50%; height: 1em; border-left: 1px solid #999; }
+.forest { padding-top: 0; }
+.forest > li { padding: 0 1em; }
+.forest > li::before, .forest > li::after { display: none; }
+.node { display: inline-block; padding: .2em .4em; border: 1px solid #999; border-radius: .3em; white-space: pre; background: #eef4fb; }
+.node small { display: block; color: #777; }
+.byte { background: #f6f6f6; }
+.direct { background: #fdf1dc; }
+";
+
+/// Escapes a DOT string literal.
+fn dot_escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Escapes HTML text and attribute values.
+fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// Caption of a node: the rank of the merge building it, which is also its token, or its token.
+fn caption<D: Display>(tree: &Tree<D>) -> String {
+    match tree.rank {
+        Some(rank) => format!("rank {rank}"),
+        None => format!("token {}", tree.token),
+    }
+}
+
+/// Renders merge trees as a Graphviz DOT digraph, one cluster per pretoken.
+///
+/// ## DOT
+/// ### Arguments
+/// * `data` - the encoded bytes.
+/// * `forests` - the byte range of every pretoken with the merge trees of its tokens.
+///
+/// ### Returns
+/// * the DOT source.
+pub fn dot<D: Display>(data: &[u8], forests: &[(Range<usize>, Vec<Tree<D>>)]) -> String {
+    fn node<D: Display>(data: &[u8], tree: &Tree<D>, next: &mut usize, output: &mut String) -> usize {
+        let id = *next;
+        *next += 1;
+        let style = match (tree.rank, tree.children.is_empty()) {
+            (Some(_), _) => "",
+            (None, true) => ", style=filled, fillcolor=\"#f6f6f6\"",
+            (None, false) => ", style=filled, fillcolor=\"#fdf1dc\"",
+        };
+        let label = dot_escape(&crate::unicode(&data[tree.range.clone()]));
+        writeln!(output, "        n{id} [label=\"{label}\\n{}\"{style}];", caption(tree)).unwrap();
+        for child in &tree.children {
+            let child = node(data, child, next, output);
+            writeln!(output, "        n{id} -> n{child};").unwrap();
+        }
+        id
+    }
+
+    let mut output = String::new();
+    writeln!(output, "digraph merges {{").unwrap();
+    writeln!(output, "    ordering=out;").unwrap();
+    writeln!(output, "    node [shape=box, fontname=\"monospace\"];").unwrap();
+    let mut next = 0;
+    for (cluster, (range, trees)) in forests.iter().enumerate() {
+        let label = dot_escape(&crate::unicode(&data[range.clone()]));
+        writeln!(output, "    subgraph cluster_{cluster} {{").unwrap();
+        writeln!(output, "        label=\"{label}\";").unwrap();
+        for tree in trees {
+            node(data, tree, &mut next, &mut output);
+        }
+        writeln!(output, "    }}").unwrap();
+    }
+    writeln!(output, "}}").unwrap();
+    output
+}
+
+/// Renders merge trees as a self-contained HTML page, one section per pretoken.
+///
+/// ## HTML
+/// ### Arguments
+/// * `data` - the encoded bytes.
+/// * `forests` - the byte range of every pretoken with the merge trees of its tokens.
+///
+/// ### Returns
+/// * the HTML page.
+pub fn html<D: Display>(data: &[u8], forests: &[(Range<usize>, Vec<Tree<D>>)]) -> String {
+    fn node<D: Display>(data: &[u8], tree: &Tree<D>, output: &mut String) {
+        let class = match (tree.rank, tree.children.is_empty()) {
+            (Some(_), _) => "node",
+            (None, true) => "node byte",
+            (None, false) => "node direct",
+        };
+        let text = html_escape(&crate::unicode(&data[tree.range.clone()]));
+        write!(output, "<li><span class=\"{class}\">{text}<small>{}</small></span>", caption(tree)).unwrap();
+        if !tree.children.is_empty() {
+            output.push_str("<ul>");
+            for child in &tree.children {
+                node(data, child, output);
+            }
+            output.push_str("</ul>");
+        };
+        output.push_str("</li>");
+    }
+
+    let mut output = String::new();
+    output.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Merge trees</title>\n");
+    writeln!(output, "<style>{STYLE}</style>\n</head>\n<body>").unwrap();
+    for (range, trees) in forests {
+        let text = html_escape(&crate::unicode(&data[range.clone()]));
+        write!(output, "<section>\n<h2>{text} <small>bytes {}..{}</small></h2>\n<ul class=\"forest\">", range.start, range.end).unwrap();
+        for tree in trees {
+            node(data, tree, &mut output);
+        }
+        output.push_str("</ul>\n</section>\n");
+    }
+    output.push_str("</body>\n</html>\n");
+    output
+}
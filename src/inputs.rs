@@ -0,0 +1,155 @@
+use std::io::Read;
+use std::path::PathBuf;
+
+/// Something the command line reads a whole text from.
+///
+/// ## Source
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    Stdin,
+    File(PathBuf),
+}
+
+impl Source {
+    /// Name reported next to the results of the source.
+    ///
+    /// ## Name
+    pub fn name(&self) -> String {
+        match self {
+            Source::Stdin => "-".to_string(),
+            Source::File(path) => path.display().to_string(),
+        }
+    }
+
+    /// Reads the whole source, line breaks included.
+    ///
+    /// ## Read
+    pub fn read(&self) -> std::io::Result<Vec<u8>> {
+        match self {
+            Source::Stdin => {
+                let mut data = vec![];
+                std::io::stdin().read_to_end(&mut data)?;
+                Ok(data)
+            }
+            Source::File(path) => std::fs::read(path),
+        }
+    }
+}
+
+/// Expands path arguments into the sources to read, in order.
+///
+/// `-` is standard input and files are taken as given.
+/// Directories are walked recursively in file name order, never entering `.git`, and skipping hidden files and
+/// whatever `.gitignore`, `.ignore` and git exclude files ignore, unless `ignore` is false.
+/// Walked files must match one of the `include` globs, when there are any, and none of the `exclude` globs.
+/// Globs follow the `.gitignore` syntax, relative to the walked directory.
+///
+/// ## Sources
+/// ### Arguments
+/// * `paths` - path arguments, standard input when empty.
+/// * `include` - globs walked files must match.
+/// * `exclude` - globs walked files must not match.
+/// * `ignore` - whether to respect ignore files and skip hidden files.
+///
+/// ### Returns
+/// * the sources, or the first invalid glob or unreadable directory.
+pub fn sources(paths: &[String], include: &[String], exclude: &[String], ignore: bool) -> Result<Vec<Source>, ignore::Error> {
+    if paths.is_empty() {
+        return Ok(vec![Source::Stdin]);
+    };
+
+    let mut sources = vec![];
+    for path in paths {
+        let path = PathBuf::from(path);
+        if path.as_os_str() == "-" {
+            sources.push(Source::Stdin);
+            continue;
+        };
+        if !path.is_dir() {
+            sources.push(Source::File(path));
+            continue;
+        };
+
+        let mut overrides = ignore::overrides::OverrideBuilder::new(&path);
+        for glob in include {
+            overrides.add(glob)?;
+        }
+        for glob in exclude {
+            overrides.add(&format!("!{glob}"))?;
+        }
+        let walker = ignore::WalkBuilder::new(&path)
+            .standard_filters(ignore)
+            .require_git(false)
+            .overrides(overrides.build()?)
+            .filter_entry(|entry| entry.file_name() != ".git")
+            .sort_by_file_name(|a, b| a.cmp(b))
+            .build();
+        for entry in walker {
+            let entry = entry?;
+            if entry.file_type().is_some_and(|kind| kind.is_file()) {
+                sources.push(Source::File(entry.into_path()));
+            };
+        }
+    }
+    Ok(sources)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Source;
+
+    /// Creates a fresh directory tree in the temporary directory, one file per relative path.
+    fn tree(name: &str, files: &[&str]) -> std::path::PathBuf {
+        let root = std::env::temp_dir().join(format!("gptbpe-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        for file in files {
+            let path = root.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, *file).unwrap();
+        }
+        root
+    }
+
+    /// Walked file paths, relative to the root.
+    fn walk(root: &std::path::Path, include: &[&str], exclude: &[&str], ignore: bool) -> Vec<String> {
+        let globs = |globs: &[&str]| globs.iter().map(|glob| glob.to_string()).collect::<Vec<String>>();
+        super::sources(&[root.display().to_string()], &globs(include), &globs(exclude), ignore)
+            .unwrap()
+            .iter()
+            .map(|source| match source {
+                Source::File(path) => path.strip_prefix(root).unwrap().display().to_string(),
+                Source::Stdin => "-".to_string(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn paths() {
+        assert_eq!(super::sources(&[], &[], &[], true).unwrap(), vec![Source::Stdin]);
+        let paths = ["-".to_string(), "missing.txt".to_string()];
+        assert_eq!(
+            super::sources(&paths, &[], &[], true).unwrap(),
+            vec![Source::Stdin, Source::File("missing.txt".into())]
+        );
+    }
+
+    #[test]
+    fn filters() {
+        let root = tree("filters", &["a.rs", "b.md", "src/c.rs", "src/d.txt"]);
+        assert_eq!(walk(&root, &[], &[], true), vec!["a.rs", "b.md", "src/c.rs", "src/d.txt"]);
+        assert_eq!(walk(&root, &["*.rs"], &[], true), vec!["a.rs", "src/c.rs"]);
+        assert_eq!(walk(&root, &[], &["*.rs"], true), vec!["b.md", "src/d.txt"]);
+        assert_eq!(walk(&root, &["*.rs", "*.txt"], &["src/"], true), vec!["a.rs"]);
+        assert!(super::sources(&[root.display().to_string()], &["[".to_string()], &[], true).is_err());
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn ignored() {
+        let root = tree("ignored", &[".gitignore", ".hidden", "kept.txt", "target/built.txt", ".git/HEAD"]);
+        std::fs::write(root.join(".gitignore"), "target/\n").unwrap();
+        assert_eq!(walk(&root, &[], &[], true), vec!["kept.txt"]);
+        assert_eq!(walk(&root, &[], &[], false), vec![".gitignore", ".hidden", "kept.txt", "target/built.txt"]);
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -8,7 +8,20 @@
 // //!
 // //! # Functions
 // //!
-mod bpe;
+pub mod bpe;
+#[cfg(unix)]
+pub mod daemon;
+pub mod rpc;
+pub mod server;
+pub mod service;
+
+// C ABI used by `gptbpe.bun.ts` and `gptbpe.deno.ts`.
+//
+// Token ids are `u16` for r50k and p50k, and `u32` for cl100k whose ids go up to 100255:
+// `encode_cl100k` and `encode_offsets_cl100k` call back with `u32` ids, and `decode_cl100k`
+// reads `length` ids of 4 bytes each, so bindings must hand it a `Uint32Array`.
+// Decoded bytes are called back as `u8`, widened to `u16` by `decode_cl100k`. Lengths and counts are `usize`.
+// The `decode_*` functions return `false` without calling back when an id is not in the vocabulary.
 
 fn read<T>(pointer: *const T, length: usize) -> &'static [T] {
     assert!(!pointer.is_null(), "[ERROR]: pointer is null.");
@@ -30,8 +43,8 @@ fn read<T>(pointer: *const T, length: usize) -> &'static [T] {
 pub extern "C" fn grapheme(buffer: *const u8, length: usize, callback: extern "C" fn (usize, u8) ) {
     let slice = read(buffer, length);
 
-    let grapheme = bpe::grapheme(slice);
-    for (idx, value) in grapheme.concat().drain(..).enumerate() {
+    let unicodes = bpe::unicodes(slice);
+    for (idx, value) in unicodes.concat().drain(..).enumerate() {
         callback(idx, value)
     };
 }
@@ -47,13 +60,37 @@ pub extern "C" fn encode_r50k(buffer: *const u8, length: usize, callback: extern
 }
 
 #[no_mangle]
-pub extern "C" fn decode_r50k(buffer: *const u16, length: usize, callback: extern "C" fn (usize, u8)) {
+pub extern "C" fn encode_offsets_r50k(buffer: *const u8, length: usize, utf16: bool, callback: extern "C" fn (usize, u16, usize, usize) ) {
     let slice = read(buffer, length);
 
-    let mut decoding = bpe::decode(slice, &crate::bpe::vocabulary::R50K_UNICODES);
+    let offsets = utf16.then(|| bpe::utf16_offsets(slice));
+    let mut encoding = bpe::encode_with_offsets(slice, &crate::bpe::vocabulary::R50K_TOKENS);
+    for (idx, (value, start, end)) in encoding.drain(..).enumerate() {
+        match &offsets {
+            Some(offsets) => callback(idx, value, offsets[start], offsets[end]),
+            None => callback(idx, value, start, end),
+        };
+    };
+}
+
+#[no_mangle]
+pub extern "C" fn count_r50k(buffer: *const u8, length: usize) -> usize {
+    let slice = read(buffer, length);
+
+    bpe::count_tokens(slice, &crate::bpe::vocabulary::R50K_TOKENS)
+}
+
+#[no_mangle]
+pub extern "C" fn decode_r50k(buffer: *const u16, length: usize, callback: extern "C" fn (usize, u8)) -> bool {
+    let slice = read(buffer, length);
+
+    let Ok(mut decoding) = bpe::decode(slice, &crate::bpe::vocabulary::R50K_UNICODES) else {
+        return false;
+    };
     for (idx, value) in decoding.drain(..).enumerate() {
         callback(idx, value)
     }
+    true
 }
 
 #[no_mangle]
@@ -67,17 +104,41 @@ pub extern "C" fn encode_p50k(buffer: *const u8, length: usize, callback: extern
 }
 
 #[no_mangle]
-pub extern "C" fn decode_p50k(buffer: *const u16, length: usize, callback: extern "C" fn (usize, u8)) {
+pub extern "C" fn encode_offsets_p50k(buffer: *const u8, length: usize, utf16: bool, callback: extern "C" fn (usize, u16, usize, usize) ) {
+    let slice = read(buffer, length);
+
+    let offsets = utf16.then(|| bpe::utf16_offsets(slice));
+    let mut encoding = bpe::encode_with_offsets(slice, &crate::bpe::vocabulary::P50K_TOKENS);
+    for (idx, (value, start, end)) in encoding.drain(..).enumerate() {
+        match &offsets {
+            Some(offsets) => callback(idx, value, offsets[start], offsets[end]),
+            None => callback(idx, value, start, end),
+        };
+    };
+}
+
+#[no_mangle]
+pub extern "C" fn count_p50k(buffer: *const u8, length: usize) -> usize {
+    let slice = read(buffer, length);
+
+    bpe::count_tokens(slice, &crate::bpe::vocabulary::P50K_TOKENS)
+}
+
+#[no_mangle]
+pub extern "C" fn decode_p50k(buffer: *const u16, length: usize, callback: extern "C" fn (usize, u8)) -> bool {
     let slice = read(buffer, length);
 
-    let mut decoding = bpe::decode(slice, &crate::bpe::vocabulary::P50K_UNICODES);
+    let Ok(mut decoding) = bpe::decode(slice, &crate::bpe::vocabulary::P50K_UNICODES) else {
+        return false;
+    };
     for (idx, value) in decoding.drain(..).enumerate() {
         callback(idx, value)
     }
+    true
 }
 
 #[no_mangle]
-pub extern "C" fn encode_cl100k(buffer: *const u8, length: usize, callback: extern "C" fn (usize, u16) ) {
+pub extern "C" fn encode_cl100k(buffer: *const u8, length: usize, callback: extern "C" fn (usize, u32) ) {
     let slice = read(buffer, length);
 
     let mut encoding = bpe::encode(slice, &crate::bpe::vocabulary::CL100K_TOKENS); 
@@ -87,12 +148,68 @@ pub extern "C" fn encode_cl100k(buffer: *const u8, length: usize, callback: exte
 }
 
 #[no_mangle]
-pub extern "C" fn decode_cl100k(buffer: *const u16, length: usize, callback: extern "C" fn (usize, u16)) {
+pub extern "C" fn encode_offsets_cl100k(buffer: *const u8, length: usize, utf16: bool, callback: extern "C" fn (usize, u32, usize, usize) ) {
+    let slice = read(buffer, length);
+
+    let offsets = utf16.then(|| bpe::utf16_offsets(slice));
+    let mut encoding = bpe::encode_with_offsets(slice, &crate::bpe::vocabulary::CL100K_TOKENS);
+    for (idx, (value, start, end)) in encoding.drain(..).enumerate() {
+        match &offsets {
+            Some(offsets) => callback(idx, value, offsets[start], offsets[end]),
+            None => callback(idx, value, start, end),
+        };
+    };
+}
+
+#[no_mangle]
+pub extern "C" fn count_cl100k(buffer: *const u8, length: usize) -> usize {
+    let slice = read(buffer, length);
+
+    bpe::count_tokens(slice, &crate::bpe::vocabulary::CL100K_TOKENS)
+}
+
+#[no_mangle]
+pub extern "C" fn decode_cl100k(buffer: *const u32, length: usize, callback: extern "C" fn (usize, u16)) -> bool {
     let slice = read(buffer, length);
 
-    let mut decoding = bpe::decode(slice, &crate::bpe::vocabulary::CL100K_UNICODES);
+    let Ok(mut decoding) = bpe::decode(slice, &crate::bpe::vocabulary::CL100K_UNICODES) else {
+        return false;
+    };
     for (idx, value) in decoding.drain(..).enumerate() {
         callback(idx, value.into())
     }
+    true
 }
 
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    static IDS: Mutex<Vec<u32>> = Mutex::new(vec![]);
+    static BYTES: Mutex<Vec<u8>> = Mutex::new(vec![]);
+
+    extern "C" fn id(_idx: usize, value: u32) {
+        IDS.lock().unwrap().push(value);
+    }
+
+    extern "C" fn byte(_idx: usize, value: u16) {
+        BYTES.lock().unwrap().push(value as u8);
+    }
+
+    #[test]
+    fn cl100k() {
+        // " mouths" is a single cl100k id above 65535, which must cross the ABI as 32 bits both ways.
+        let text = "hello mouths";
+        super::encode_cl100k(text.as_ptr(), text.len(), id);
+        let ids = IDS.lock().unwrap().clone();
+        assert_eq!(ids, [15339, 65609]);
+        assert_eq!(super::count_cl100k(text.as_ptr(), text.len()), 2);
+
+        assert!(super::decode_cl100k(ids.as_ptr(), ids.len(), byte));
+        assert_eq!(BYTES.lock().unwrap().as_slice(), text.as_bytes());
+
+        BYTES.lock().unwrap().clear();
+        assert!(!super::decode_cl100k([15339, 100256].as_ptr(), 2, byte));
+        assert!(BYTES.lock().unwrap().is_empty());
+    }
+}
@@ -1,17 +1,1007 @@
-mod bpe;
+mod compare;
+mod graph;
+mod inputs;
+mod show;
+mod vocab;
+
 use argh::FromArgs;
+use gptbpe::bpe;
+use gptbpe::bpe::vocabulary::Encoding;
+use std::collections::BTreeMap;
 use std::io::stdin;
+use std::io::stdout;
+use std::io::IsTerminal;
+use std::io::Read;
+use std::io::Write;
+use std::sync::LazyLock;
 
 /// Encoder command line instructions
 #[derive(Debug, FromArgs)]
-struct Encode {}
+struct Arguments {
+    #[argh(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, FromArgs)]
+#[argh(subcommand)]
+enum Command {
+    Encode(Encode),
+    Decode(Decode),
+    Count(Count),
+    Show(Show),
+    Explain(Explain),
+    Tree(Tree),
+    Vocab(Vocab),
+    Compare(Compare),
+    ChatCount(ChatCount),
+    Cost(Cost),
+    Serve(Serve),
+    Daemon(Daemon),
+    Rpc(Rpc),
+    Truncate(Truncate),
+    Chunk(Chunk),
+}
+
+/// Output format of the `encode` subcommand.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    /// A JSON object with the text, ids and count of the whole input.
+    Json,
+    /// A JSON object with text, ids and count per input line.
+    Jsonl,
+    /// Ids separated by spaces on one line.
+    Ids,
+    /// One token per row: id, byte start, byte end and escaped text.
+    Tsv,
+    /// Little-endian ids, two bytes wide for 16 bit vocabularies and four bytes wide otherwise.
+    Binary,
+}
+
+impl std::str::FromStr for Format {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format {
+            "json" => Ok(Format::Json),
+            "jsonl" => Ok(Format::Jsonl),
+            "ids" => Ok(Format::Ids),
+            "tsv" => Ok(Format::Tsv),
+            "binary" => Ok(Format::Binary),
+            _ => Err(format!("[ERROR]: Unknown format '{format}', expected one of 'json', 'jsonl', 'ids', 'tsv' or 'binary'.")),
+        }
+    }
+}
+
+/// Encode text from the arguments, or standard input, into token ids
+#[derive(Debug, FromArgs)]
+#[argh(subcommand, name = "encode")]
+struct Encode {
+    /// vocabulary, `r50k` (default), `p50k`, `cl100k` or `o200k`
+    #[argh(option, default = "Encoding::R50K")]
+    encoding: Encoding,
+
+    /// output format, `ids` (default), `json`, `jsonl`, `tsv` or `binary`
+    #[argh(option, default = "Format::Ids")]
+    format: Format,
+
+    /// text to encode, not a file path, standard input when empty
+    #[argh(positional, greedy)]
+    text: Vec<String>,
+}
+
+/// Decode token ids from the arguments, or standard input, back into text
+#[derive(Debug, FromArgs)]
+#[argh(subcommand, name = "decode")]
+struct Decode {
+    /// vocabulary, `r50k` (default), `p50k`, `cl100k` or `o200k`
+    #[argh(option, default = "Encoding::R50K")]
+    encoding: Encoding,
+
+    /// token ids to decode, standard input (whitespace or comma separated) when empty
+    #[argh(positional)]
+    ids: Vec<u32>,
+}
+
+/// Count the tokens of files, directories, or standard input, each read as a whole
+#[derive(Debug, FromArgs)]
+#[argh(subcommand, name = "count")]
+struct Count {
+    /// vocabulary, `r50k` (default), `p50k`, `cl100k` or `o200k`
+    #[argh(option, default = "Encoding::R50K")]
+    encoding: Encoding,
+
+    /// only count files in directories matching this glob, repeatable
+    #[argh(option)]
+    include: Vec<String>,
+
+    /// skip files in directories matching this glob, repeatable
+    #[argh(option)]
+    exclude: Vec<String>,
+
+    /// also count hidden files and files ignored by `.gitignore`
+    #[argh(switch)]
+    no_ignore: bool,
+
+    /// files and directories to count, `-` or empty for standard input
+    #[argh(positional)]
+    paths: Vec<String>,
+}
+
+/// When the `show` subcommand colors its output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Color {
+    /// When standard output is a terminal and `NO_COLOR` is not set.
+    Auto,
+    Always,
+    Never,
+}
+
+impl std::str::FromStr for Color {
+    type Err = String;
+
+    fn from_str(color: &str) -> Result<Self, Self::Err> {
+        match color {
+            "auto" => Ok(Color::Auto),
+            "always" => Ok(Color::Always),
+            "never" => Ok(Color::Never),
+            _ => Err(format!("[ERROR]: Unknown color mode '{color}', expected 'auto', 'always' or 'never'.")),
+        }
+    }
+}
+
+/// Print text from the arguments, or standard input, with its token boundaries visible
+#[derive(Debug, FromArgs)]
+#[argh(subcommand, name = "show")]
+struct Show {
+    /// vocabulary, `r50k` (default), `p50k`, `cl100k` or `o200k`
+    #[argh(option, default = "Encoding::R50K")]
+    encoding: Encoding,
+
+    /// annotate every token with its id
+    #[argh(switch)]
+    ids: bool,
+
+    /// alternate background colors per token, `auto` (default), `always` or `never`; `never` separates tokens with `|`
+    #[argh(option, default = "Color::Auto")]
+    color: Color,
+
+    /// text to show, standard input when empty
+    #[argh(positional, greedy)]
+    text: Vec<String>,
+}
+
+/// List, for each pretoken of the arguments or standard input, the merges applied with their ranks
+#[derive(Debug, FromArgs)]
+#[argh(subcommand, name = "explain")]
+struct Explain {
+    /// vocabulary, `r50k` (default), `p50k`, `cl100k` or `o200k`
+    #[argh(option, default = "Encoding::R50K")]
+    encoding: Encoding,
+
+    /// print one JSON object per pretoken instead
+    #[argh(switch)]
+    json: bool,
+
+    /// text to explain, standard input when empty
+    #[argh(positional, greedy)]
+    text: Vec<String>,
+}
+
+/// Output format of the `tree` subcommand.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Graph {
+    /// Graphviz digraph.
+    Dot,
+    /// Self-contained HTML page.
+    Html,
+}
+
+impl std::str::FromStr for Graph {
+    type Err = String;
+
+    fn from_str(graph: &str) -> Result<Self, Self::Err> {
+        match graph {
+            "dot" => Ok(Graph::Dot),
+            "html" => Ok(Graph::Html),
+            _ => Err(format!("[ERROR]: Unknown format '{graph}', expected 'dot' or 'html'.")),
+        }
+    }
+}
+
+/// Export the binary merge tree of every token of the arguments, or standard input
+#[derive(Debug, FromArgs)]
+#[argh(subcommand, name = "tree")]
+struct Tree {
+    /// vocabulary, `r50k` (default), `p50k`, `cl100k` or `o200k`
+    #[argh(option, default = "Encoding::R50K")]
+    encoding: Encoding,
+
+    /// output format, `dot` (default) or `html`
+    #[argh(option, default = "Graph::Dot")]
+    format: Graph,
+
+    /// text to export, standard input when empty
+    #[argh(positional, greedy)]
+    text: Vec<String>,
+}
+
+/// Inspect the vocabulary, printing the id, GPT unicode form and decoded bytes of tokens
+#[derive(Debug, FromArgs)]
+#[argh(subcommand, name = "vocab")]
+struct Vocab {
+    /// vocabulary, `r50k` (default), `p50k`, `cl100k` or `o200k`
+    #[argh(option, default = "Encoding::R50K")]
+    encoding: Encoding,
+
+    #[argh(subcommand)]
+    command: VocabCommand,
+}
+
+#[derive(Debug, FromArgs)]
+#[argh(subcommand)]
+enum VocabCommand {
+    Get(VocabGet),
+    Find(VocabFind),
+    Search(VocabSearch),
+}
+
+/// Print the tokens with the given ids
+#[derive(Debug, FromArgs)]
+#[argh(subcommand, name = "get")]
+struct VocabGet {
+    /// token ids
+    #[argh(positional)]
+    ids: Vec<u32>,
+}
+
+/// Print the token spelled exactly as a string
+#[derive(Debug, FromArgs)]
+#[argh(subcommand, name = "find")]
+struct VocabFind {
+    /// read the string in the GPT unicode scheme (`Ġhello`) instead of as text (` hello`)
+    #[argh(switch)]
+    unicode: bool,
+
+    /// the string to find
+    #[argh(positional)]
+    string: String,
+}
+
+/// Print the tokens matching a prefix, a regular expression, or both
+#[derive(Debug, FromArgs)]
+#[argh(subcommand, name = "search")]
+struct VocabSearch {
+    /// text the decoded token starts with
+    #[argh(option)]
+    prefix: Option<String>,
+
+    /// regular expression the decoded token matches
+    #[argh(option)]
+    regex: Option<String>,
+
+    /// maximum number of tokens to print
+    #[argh(option)]
+    limit: Option<usize>,
+}
+
+/// Compare token counts, bytes per token and token boundaries of a file, or standard input, across encodings
+#[derive(Debug, FromArgs)]
+#[argh(subcommand, name = "compare")]
+struct Compare {
+    /// comma separated encodings, every available one by default
+    #[argh(option, from_str_fn(encodings))]
+    encodings: Option<Vec<Encoding>>,
+
+    /// only print the statistics, without the token boundaries
+    #[argh(switch)]
+    summary: bool,
+
+    /// file to compare, standard input when missing
+    #[argh(positional)]
+    path: Option<String>,
+}
+
+/// Count the prompt tokens of a JSON array of chat messages, per-message framing included
+#[derive(Debug, FromArgs)]
+#[argh(subcommand, name = "chat-count")]
+struct ChatCount {
+    /// model whose framing and encoding are used
+    #[argh(option, default = "String::from(\"gpt-4\")")]
+    model: String,
+
+    /// encoding overriding the one of the model
+    #[argh(option)]
+    encoding: Option<Encoding>,
+
+    /// tokens added to every message, overriding the model
+    #[argh(option)]
+    per_message: Option<i64>,
+
+    /// tokens added by the name of a message, overriding the model
+    #[argh(option)]
+    per_name: Option<i64>,
+
+    /// tokens priming the reply, overriding the model
+    #[argh(option)]
+    reply: Option<i64>,
+
+    /// JSON file of tool definitions with JSON Schema parameters, counted alongside the messages
+    #[argh(option)]
+    tools: Option<String>,
+
+    /// print the tool definitions as the model receives them instead of counting
+    #[argh(switch)]
+    render_tools: bool,
+
+    /// JSON file of `{role, content, name}` messages, standard input when missing
+    #[argh(positional)]
+    path: Option<String>,
+}
+
+/// Estimate the cost of sending files to a model, from a local price table
+#[derive(Debug, FromArgs)]
+#[argh(subcommand, name = "cost")]
+struct Cost {
+    /// model to price, looked up in the price table
+    #[argh(option)]
+    model: String,
+
+    /// file or directory to send, `-` or none for standard input, repeatable
+    #[argh(option)]
+    input: Vec<String>,
+
+    /// completion tokens expected for every input
+    #[argh(option, default = "0")]
+    expected_output: usize,
+
+    /// price table, a JSON object of models
+    #[argh(option, default = "String::from(\"prices.json\")")]
+    prices: String,
+}
+
+/// Parses a comma separated list of encodings.
+fn encodings(list: &str) -> Result<Vec<Encoding>, String> {
+    list.split(',').map(|encoding| encoding.trim().parse()).collect()
+}
+
+/// Serve `POST /encode`, `/decode`, `/count`, `/truncate` and `/chunk` over HTTP with JSON bodies
+#[derive(Debug, FromArgs)]
+#[argh(subcommand, name = "serve")]
+struct Serve {
+    /// address to listen on
+    #[argh(option, default = "String::from(\"127.0.0.1:8080\")")]
+    listen: String,
+}
+
+/// Serve a length-prefixed binary protocol on a Unix domain socket, see the `daemon` module
+#[derive(Debug, FromArgs)]
+#[argh(subcommand, name = "daemon")]
+struct Daemon {
+    /// path of the socket, replaced if a stale socket is left there
+    #[argh(option, default = "String::from(\"/tmp/gptbpe.sock\")")]
+    socket: String,
+}
+
+/// Answer newline-delimited JSON-RPC 2.0 requests (`encode`, `decode`, `count`, `truncate`, `chunk`) from standard input
+#[derive(Debug, FromArgs)]
+#[argh(subcommand, name = "rpc")]
+struct Rpc {}
+
+/// Truncate standard input to a number of tokens
+#[derive(Debug, FromArgs)]
+#[argh(subcommand, name = "truncate")]
+struct Truncate {
+    /// vocabulary, `r50k` (default), `p50k`, `cl100k` or `o200k`
+    #[argh(option, default = "Encoding::R50K")]
+    encoding: Encoding,
+
+    /// maximum number of tokens to keep
+    #[argh(option, short = 'n')]
+    max_tokens: usize,
+
+    /// side to cut tokens from, `start` or `end` (default)
+    #[argh(option, default = "bpe::Side::End")]
+    side: bpe::Side,
+}
+
+/// Split standard input into overlapping token windows, printed as JSON lines
+#[derive(Debug, FromArgs)]
+#[argh(subcommand, name = "chunk")]
+struct Chunk {
+    /// vocabulary, `r50k` (default), `p50k`, `cl100k` or `o200k`
+    #[argh(option, default = "Encoding::R50K")]
+    encoding: Encoding,
+
+    /// maximum number of tokens per chunk
+    #[argh(option)]
+    size: usize,
+
+    /// number of tokens repeated at the start of the next chunk
+    #[argh(option, default = "0")]
+    overlap: usize,
+
+    /// number of tokens a chunk may give up to end on a paragraph, sentence or pretoken boundary
+    #[argh(option, default = "0")]
+    slack: usize,
+
+    /// keep `markdown` sections and code blocks, or `code` top-level items, whole when they fit
+    #[argh(option)]
+    structure: Option<bpe::chunk::Format>,
+}
+
+/// Text of the positional arguments joined by spaces, or all of standard input.
+fn text(arguments: &[String]) -> Vec<u8> {
+    if !arguments.is_empty() {
+        return arguments.join(" ").into_bytes();
+    };
+    let mut data = vec![];
+    stdin().read_to_end(&mut data).unwrap_or_else(|error| fail(&format!("[ERROR]: Could not read standard input: {error}")));
+    data
+}
+
+/// Token ids of the positional arguments, or parsed from standard input.
+fn ids(arguments: &[u32]) -> Vec<u32> {
+    if !arguments.is_empty() {
+        return arguments.to_vec();
+    };
+    let mut data = String::new();
+    stdin().read_to_string(&mut data).unwrap_or_else(|error| fail(&format!("[ERROR]: Could not read standard input: {error}")));
+    data.split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|id| !id.is_empty())
+        .map(|id| match id.parse::<u32>() {
+            Ok(id) => id,
+            Err(_) => fail(&format!("[ERROR]: '{id}' is not a token id.")),
+        })
+        .collect()
+}
+
+/// Escapes tabs, line breaks and backslashes of a token so it stays on one TSV field.
+fn escape(token: &[u8]) -> String {
+    String::from_utf8_lossy(token)
+        .replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+/// Encodes the input of the `encode` subcommand and writes it in the requested format.
+fn encode<D>(encode: Encode, tokens: &LazyLock<BTreeMap<Vec<u8>, D>>, out: &mut impl Write) -> std::io::Result<()>
+where
+    D: std::clone::Clone + std::fmt::Display,
+    usize: TryFrom<D>,
+    u32: From<D>,
+{
+    let encoder = bpe::encoder::Encoder::new(tokens);
+    match encode.format {
+        Format::Ids | Format::Binary => {
+            let ids: Vec<D> = match encode.text.is_empty() {
+                true => encoder.encode_reader(stdin().lock()).collect::<std::io::Result<_>>()?,
+                false => encoder.encode(&text(&encode.text)),
+            };
+            if encode.format == Format::Binary {
+                for id in ids {
+                    out.write_all(&u32::from(id).to_le_bytes()[..std::mem::size_of::<D>()])?;
+                }
+                return Ok(());
+            };
+            let line: Vec<String> = ids.iter().map(|id| id.to_string()).collect();
+            writeln!(out, "{}", line.join(" "))
+        }
+        Format::Json => {
+            let data = text(&encode.text);
+            let ids = encoder.encode(&data);
+            let object = serde_json::json!({
+                "text": String::from_utf8_lossy(&data),
+                "count": ids.len(),
+                "ids": ids.into_iter().map(u32::from).collect::<Vec<u32>>(),
+            });
+            writeln!(out, "{object}")
+        }
+        Format::Jsonl => {
+            let data = text(&encode.text);
+            for record in data.split_inclusive(|byte| *byte == b'\n') {
+                let record = record.strip_suffix(b"\n").unwrap_or(record);
+                let record = record.strip_suffix(b"\r").unwrap_or(record);
+                let ids = encoder.encode(record);
+                let object = serde_json::json!({
+                    "text": String::from_utf8_lossy(record),
+                    "count": ids.len(),
+                    "ids": ids.into_iter().map(u32::from).collect::<Vec<u32>>(),
+                });
+                writeln!(out, "{object}")?;
+            }
+            Ok(())
+        }
+        Format::Tsv => {
+            let data = text(&encode.text);
+            writeln!(out, "id\tstart\tend\ttext")?;
+            for (id, start, end) in bpe::encode_with_offsets(&data, tokens) {
+                writeln!(out, "{id}\t{start}\t{end}\t{}", escape(&data[start..end]))?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Bytes in the GPT unicode scheme, the way vocabularies and merge lists spell symbols.
+fn unicode(bytes: &[u8]) -> String {
+    String::from_utf8(bpe::unicodes(bytes).concat()).unwrap()
+}
+
+/// Writes the merge traces of the `explain` subcommand.
+fn explain<D>(explain: Explain, tokens: &LazyLock<BTreeMap<Vec<u8>, D>>, out: &mut impl Write) -> std::io::Result<()>
+where
+    D: std::clone::Clone,
+    usize: TryFrom<D>,
+    u32: From<D>,
+{
+    let data = text(&explain.text);
+    let spell = |symbols: &[(D, std::ops::Range<usize>)]| -> Vec<String> {
+        symbols.iter().map(|(_, range)| unicode(&data[range.clone()])).collect()
+    };
+    for trace in bpe::explain(&data, tokens) {
+        let ids: Vec<u32> = trace.tokens.iter().cloned().map(u32::from).collect();
+        if explain.json {
+            let merges: Vec<serde_json::Value> = trace
+                .merges
+                .iter()
+                .map(|merge| {
+                    serde_json::json!({
+                        "rank": merge.rank,
+                        "left": unicode(&data[merge.pair.0.clone()]),
+                        "right": unicode(&data[merge.pair.1.clone()]),
+                        "symbols": spell(&merge.symbols),
+                    })
+                })
+                .collect();
+            let object = serde_json::json!({
+                "text": unicode(&data[trace.range.clone()]),
+                "start": trace.range.start,
+                "end": trace.range.end,
+                "direct": trace.direct,
+                "bytes": spell(&trace.bytes),
+                "merges": merges,
+                "tokens": ids,
+            });
+            writeln!(out, "{object}")?;
+            continue;
+        };
+
+        let text = unicode(&data[trace.range.clone()]);
+        match trace.direct {
+            true => writeln!(out, "{text:?} {}..{}: vocabulary token {ids:?}", trace.range.start, trace.range.end)?,
+            false => writeln!(out, "{text:?} {}..{}:", trace.range.start, trace.range.end)?,
+        };
+        if trace.merges.is_empty() {
+            continue;
+        };
+        writeln!(out, "    bytes        {}", spell(&trace.bytes).join(" "))?;
+        for merge in &trace.merges {
+            let left = unicode(&data[merge.pair.0.clone()]);
+            let right = unicode(&data[merge.pair.1.clone()]);
+            writeln!(out, "    rank {:>7} {left} + {right} -> {}", merge.rank, spell(&merge.symbols).join(" "))?;
+        }
+        writeln!(out, "    tokens       {ids:?}")?;
+    }
+    Ok(())
+}
+
+/// Runs the `vocab` subcommands.
+fn vocab<D>(
+    command: VocabCommand,
+    tokens: &LazyLock<BTreeMap<Vec<u8>, D>>,
+    unicodes: &LazyLock<BTreeMap<D, Vec<u8>>>,
+    out: &mut impl Write,
+) -> std::io::Result<()>
+where
+    D: std::clone::Clone + std::cmp::Ord + std::fmt::Display + TryFrom<u32>,
+{
+    match command {
+        VocabCommand::Get(get) => {
+            for id in get.ids {
+                match D::try_from(id).ok().and_then(|token| unicodes.get_key_value(&token)) {
+                    Some((token, form)) => vocab::entry(out, token, form)?,
+                    None => fail(&format!("[ERROR]: Token id {id} is not in the vocabulary.")),
+                };
+            }
+        }
+        VocabCommand::Find(find) => {
+            let form = match find.unicode {
+                true => find.string.into_bytes(),
+                false => bpe::unicodes(find.string.as_bytes()).concat(),
+            };
+            match tokens.get(&form) {
+                Some(token) => vocab::entry(out, token, &form)?,
+                None => fail(&format!("[ERROR]: {:?} is not a token.", String::from_utf8_lossy(&form))),
+            };
+        }
+        VocabCommand::Search(search) => {
+            let regex = search.regex.map(|pattern| match regex::bytes::Regex::new(&pattern) {
+                Ok(regex) => regex,
+                Err(error) => fail(&format!("[ERROR]: {error}")),
+            });
+            let candidates: Vec<(D, &[u8])> = match &search.prefix {
+                Some(prefix) => vocab::prefixed(tokens, prefix.as_bytes()),
+                None => unicodes.iter().map(|(token, form)| (token.clone(), form.as_slice())).collect(),
+            };
+            let matches = candidates
+                .into_iter()
+                .filter(|(_, form)| regex.as_ref().is_none_or(|regex| regex.is_match(&bpe::bytes(form))))
+                .take(search.limit.unwrap_or(usize::MAX));
+            for (token, form) in matches {
+                vocab::entry(out, &token, form)?;
+            }
+        }
+    };
+    Ok(())
+}
+
+/// Prints an error and exits with a failure status.
+fn fail(message: &str) -> ! {
+    eprintln!("{message}");
+    std::process::exit(1)
+}
+
+/// Subcommands run against the tokens and unicodes of one vocabulary.
+#[derive(Debug)]
+enum Tokenized {
+    Encode(Encode),
+    Decode(Decode),
+    Count(Count),
+    Show(Show),
+    Explain(Explain),
+    Tree(Tree),
+    Vocab(VocabCommand),
+}
+
+/// Runs a vocabulary dependent command against the tokens and unicodes of a vocabulary.
+fn run<D>(command: Tokenized, tokens: &LazyLock<BTreeMap<Vec<u8>, D>>, unicodes: &LazyLock<BTreeMap<D, Vec<u8>>>)
+where
+    D: std::clone::Clone + std::cmp::Ord + std::fmt::Debug + std::fmt::Display + TryFrom<u32>,
+    usize: TryFrom<D>,
+    u32: From<D>,
+{
+    let mut out = std::io::BufWriter::new(stdout().lock());
+    match command {
+        Tokenized::Encode(arguments) => encode(arguments, tokens, &mut out).unwrap(),
+        Tokenized::Decode(decode) => {
+            let ids: Vec<D> = ids(&decode.ids)
+                .into_iter()
+                .map(|id| match D::try_from(id) {
+                    Ok(token) => token,
+                    Err(_) => fail(&format!("[ERROR]: Token id {id} is not in the vocabulary.")),
+                })
+                .collect();
+            match bpe::decode(&ids, unicodes) {
+                Ok(slice) => out.write_all(&slice).unwrap(),
+                Err(id) => fail(&format!("[ERROR]: Token id {id} is not in the vocabulary.")),
+            };
+        }
+        Tokenized::Count(count) => {
+            let sources = match inputs::sources(&count.paths, &count.include, &count.exclude, !count.no_ignore) {
+                Ok(sources) => sources,
+                Err(error) => fail(&format!("[ERROR]: {error}")),
+            };
+            let mut total = 0;
+            let mut failed = false;
+            for source in &sources {
+                match source.read() {
+                    Ok(data) => {
+                        let count = bpe::count_tokens(&data, tokens);
+                        total += count;
+                        match sources == [inputs::Source::Stdin] {
+                            true => writeln!(out, "{count}").unwrap(),
+                            false => writeln!(out, "{count}\t{}", source.name()).unwrap(),
+                        };
+                    }
+                    Err(error) => {
+                        eprintln!("[ERROR]: {}: {error}", source.name());
+                        failed = true;
+                    }
+                };
+            }
+            if sources.len() > 1 {
+                writeln!(out, "{total}\ttotal").unwrap();
+            };
+            if failed {
+                out.flush().unwrap();
+                std::process::exit(1);
+            };
+        }
+        Tokenized::Show(show) => {
+            let data = text(&show.text);
+            let tokens: Vec<(u32, usize, usize)> = bpe::encode_with_offsets(&data, tokens)
+                .into_iter()
+                .map(|(id, start, end)| (u32::from(id), start, end))
+                .collect();
+            let color = match show.color {
+                Color::Auto => stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none(),
+                Color::Always => true,
+                Color::Never => false,
+            };
+            write!(out, "{}", show::render(&data, &tokens, show.ids, color)).unwrap();
+        }
+        Tokenized::Explain(arguments) => explain(arguments, tokens, &mut out).unwrap(),
+        Tokenized::Tree(tree) => {
+            let data = text(&tree.text);
+            let forests: Vec<_> = bpe::explain(&data, tokens)
+                .iter()
+                .map(|trace| (trace.range.clone(), bpe::tree::trees(trace)))
+                .collect();
+            match tree.format {
+                Graph::Dot => write!(out, "{}", graph::dot(&data, &forests)).unwrap(),
+                Graph::Html => write!(out, "{}", graph::html(&data, &forests)).unwrap(),
+            };
+        }
+        Tokenized::Vocab(command) => vocab(command, tokens, unicodes, &mut out).unwrap(),
+    };
+}
+
+/// Fails when the vocabulary file of an encoding is missing.
+fn available(encoding: Encoding) {
+    if !encoding.available() {
+        fail(&format!("[ERROR]: Vocabulary file '{}' not found.", encoding.path()));
+    };
+}
+
+/// Runs a vocabulary dependent command with the tables of an encoding.
+fn dispatch(encoding: Encoding, command: Tokenized) {
+    available(encoding);
+    match encoding {
+        Encoding::R50K => run(command, &bpe::vocabulary::R50K_TOKENS, &bpe::vocabulary::R50K_UNICODES),
+        Encoding::P50K => run(command, &bpe::vocabulary::P50K_TOKENS, &bpe::vocabulary::P50K_UNICODES),
+        Encoding::CL100K => run(command, &bpe::vocabulary::CL100K_TOKENS, &bpe::vocabulary::CL100K_UNICODES),
+        Encoding::O200K => run(command, &bpe::vocabulary::O200K_TOKENS, &bpe::vocabulary::O200K_UNICODES),
+    };
+}
+
+/// Runs the `serve` subcommand.
+fn serve(serve: Serve) {
+    let listener = std::net::TcpListener::bind(&serve.listen)
+        .unwrap_or_else(|error| fail(&format!("[ERROR]: Could not listen on {}: {error}", serve.listen)));
+    eprintln!("[INFO]: Listening on http://{}", listener.local_addr().unwrap());
+    if let Err(error) = gptbpe::server::serve(listener) {
+        fail(&format!("[ERROR]: {error}"));
+    };
+}
+
+/// Runs the `rpc` subcommand.
+fn rpc() {
+    if let Err(error) = gptbpe::rpc::serve(stdin().lock(), stdout().lock()) {
+        fail(&format!("[ERROR]: {error}"));
+    };
+}
+
+/// Runs the `daemon` subcommand.
+fn daemon(daemon: Daemon) {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::FileTypeExt;
+        // Only a socket nobody answers on is stale, a live one belongs to another daemon.
+        if std::fs::symlink_metadata(&daemon.socket).is_ok_and(|metadata| metadata.file_type().is_socket()) {
+            match std::os::unix::net::UnixStream::connect(&daemon.socket) {
+                Ok(_) => fail(&format!("[ERROR]: A daemon is already running on {}.", daemon.socket)),
+                Err(error) if error.kind() == std::io::ErrorKind::ConnectionRefused => std::fs::remove_file(&daemon.socket)
+                    .unwrap_or_else(|error| fail(&format!("[ERROR]: Could not remove {}: {error}", daemon.socket))),
+                Err(error) => fail(&format!("[ERROR]: Could not probe {}: {error}", daemon.socket)),
+            };
+        };
+        let listener = std::os::unix::net::UnixListener::bind(&daemon.socket)
+            .unwrap_or_else(|error| fail(&format!("[ERROR]: Could not listen on {}: {error}", daemon.socket)));
+        eprintln!("[INFO]: Listening on {}", daemon.socket);
+        if let Err(error) = gptbpe::daemon::serve(listener) {
+            fail(&format!("[ERROR]: {error}"));
+        };
+    }
+    #[cfg(not(unix))]
+    fail(&format!("[ERROR]: Unix domain sockets are not supported here, cannot listen on {}.", daemon.socket));
+}
+
+/// Runs the `chat-count` subcommand.
+fn chat_count(chat: ChatCount) {
+    let data = match &chat.path {
+        Some(path) => std::fs::read(path).unwrap_or_else(|error| fail(&format!("[ERROR]: {path}: {error}"))),
+        None => text(&[]),
+    };
+    let mut framing = bpe::chat::Framing::model(&chat.model).unwrap_or_else(|error| fail(&error));
+    framing.encoding = chat.encoding.unwrap_or(framing.encoding);
+    framing.per_message = chat.per_message.unwrap_or(framing.per_message);
+    framing.per_name = chat.per_name.unwrap_or(framing.per_name);
+    framing.reply = chat.reply.unwrap_or(framing.reply);
+    available(framing.encoding);
+    let tools: serde_json::Value = match &chat.tools {
+        Some(path) => std::fs::read(path)
+            .map_err(|error| format!("[ERROR]: {path}: {error}"))
+            .and_then(|data| serde_json::from_slice(&data).map_err(|error| format!("[ERROR]: Invalid JSON in {path}: {error}")))
+            .unwrap_or_else(|error| fail(&error)),
+        None => serde_json::json!([]),
+    };
+    if chat.render_tools {
+        println!("{}", bpe::tools::render(&tools).unwrap_or_else(|error| fail(&error)));
+        return;
+    };
+    let messages = serde_json::from_slice(&data)
+        .map_err(|error| format!("[ERROR]: Invalid JSON: {error}"))
+        .and_then(|value| bpe::chat::messages(&value))
+        .unwrap_or_else(|error| fail(&error));
+    println!("{}", bpe::chat::prompt(&messages, &tools, &framing).unwrap_or_else(|error| fail(&error)));
+}
+
+/// Runs the `cost` subcommand.
+fn cost(cost: Cost) {
+    let prices = bpe::cost::table(std::path::Path::new(&cost.prices)).unwrap_or_else(|error| fail(&error));
+    let Some(price) = prices.get(&cost.model) else {
+        fail(&format!("[ERROR]: Model '{}' is not in the price table {}.", cost.model, cost.prices));
+    };
+    let encoding = match price.encoding {
+        Some(encoding) => encoding,
+        None => bpe::chat::Framing::model(&cost.model)
+            .map(|framing| framing.encoding)
+            .unwrap_or_else(|_| fail(&format!("[ERROR]: No encoding known for '{}', set one in the price table.", cost.model))),
+    };
+    available(encoding);
+    let sources = inputs::sources(&cost.input, &[], &[], true).unwrap_or_else(|error| fail(&format!("[ERROR]: {error}")));
+
+    let mut out = std::io::BufWriter::new(stdout().lock());
+    writeln!(out, "input\toutput\tcost\tname").unwrap();
+    let (mut input, mut output, mut total) = (0, 0, 0.0);
+    let mut failed = false;
+    for source in &sources {
+        match source.read() {
+            Ok(data) => {
+                let tokens = encoding.count(&data);
+                let price = price.cost(tokens, cost.expected_output);
+                (input, output, total) = (input + tokens, output + cost.expected_output, total + price);
+                writeln!(out, "{tokens}\t{}\t{price:.6}\t{}", cost.expected_output, source.name()).unwrap();
+            }
+            Err(error) => {
+                eprintln!("[ERROR]: {}: {error}", source.name());
+                failed = true;
+            }
+        };
+    }
+    if sources.len() > 1 {
+        writeln!(out, "{input}\t{output}\t{total:.6}\ttotal").unwrap();
+    };
+    out.flush().unwrap();
+    if failed {
+        std::process::exit(1);
+    };
+}
+
+/// Runs the `compare` subcommand.
+fn compare(compare: Compare) {
+    let data = match &compare.path {
+        Some(path) => std::fs::read(path).unwrap_or_else(|error| fail(&format!("[ERROR]: {path}: {error}"))),
+        None => text(&[]),
+    };
+    let encodings: Vec<Encoding> = match compare.encodings {
+        Some(encodings) => encodings
+            .into_iter()
+            .filter(|encoding| {
+                if !encoding.available() {
+                    eprintln!("[WARNING]: Vocabulary file '{}' not found, skipping {}.", encoding.path(), encoding.name());
+                };
+                encoding.available()
+            })
+            .collect(),
+        None => Encoding::ALL.into_iter().filter(Encoding::available).collect(),
+    };
+    let mut out = std::io::BufWriter::new(stdout().lock());
+    write!(out, "{}", compare::render(&data, &encodings, compare.summary)).unwrap();
+}
+
+/// Runs the `truncate` subcommand.
+fn truncate(truncate: Truncate) {
+    available(truncate.encoding);
+    let mut data = String::new();
+    stdin().read_to_string(&mut data).unwrap_or_else(|error| fail(&format!("[ERROR]: Could not read standard input: {error}")));
+    let (text, _) = truncate.encoding.truncate(&data, truncate.max_tokens, truncate.side);
+    print!("{text}");
+}
+
+/// Runs the `chunk` subcommand, printing every chunk as a JSON line.
+fn chunk(chunk: Chunk) {
+    available(chunk.encoding);
+    let mut data = String::new();
+    stdin().read_to_string(&mut data).unwrap_or_else(|error| fail(&format!("[ERROR]: Could not read standard input: {error}")));
+    let chunks = match chunk.structure {
+        Some(format) => chunk.encoding.structured(&data, chunk.size, format),
+        None => chunk.encoding.chunk(&data, chunk.size, chunk.overlap, chunk.slack),
+    };
+    let chunks = chunks.unwrap_or_else(|error| fail(&error));
+    let mut out = std::io::BufWriter::new(stdout().lock());
+    for c in chunks {
+        let line = serde_json::json!({
+            "text": c.text,
+            "tokens": c.tokens,
+            "start": c.range.start,
+            "end": c.range.end,
+        });
+        writeln!(out, "{line}").unwrap();
+    }
+}
 
 fn main() {
-    let _arguments: Encode = argh::from_env();
+    let arguments: Arguments = argh::from_env();
+    match arguments.command {
+        Command::Encode(encode) => dispatch(encode.encoding, Tokenized::Encode(encode)),
+        Command::Decode(decode) => dispatch(decode.encoding, Tokenized::Decode(decode)),
+        Command::Count(count) => dispatch(count.encoding, Tokenized::Count(count)),
+        Command::Show(show) => dispatch(show.encoding, Tokenized::Show(show)),
+        Command::Explain(explain) => dispatch(explain.encoding, Tokenized::Explain(explain)),
+        Command::Tree(tree) => dispatch(tree.encoding, Tokenized::Tree(tree)),
+        Command::Vocab(vocab) => dispatch(vocab.encoding, Tokenized::Vocab(vocab.command)),
+        Command::Compare(arguments) => compare(arguments),
+        Command::ChatCount(arguments) => chat_count(arguments),
+        Command::Cost(arguments) => cost(arguments),
+        Command::Serve(arguments) => serve(arguments),
+        Command::Daemon(arguments) => daemon(arguments),
+        Command::Rpc(_) => rpc(),
+        Command::Truncate(arguments) => truncate(arguments),
+        Command::Chunk(arguments) => chunk(arguments),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Encode;
+    use super::Format;
+    use gptbpe::bpe::vocabulary::Encoding;
+
+    /// Output of the `encode` subcommand for a text given as arguments.
+    fn encode(encoding: Encoding, format: Format, text: &str) -> Vec<u8> {
+        let arguments = Encode { encoding, format, text: vec![text.to_string()] };
+        let mut out = vec![];
+        match encoding {
+            Encoding::CL100K => super::encode(arguments, &gptbpe::bpe::vocabulary::CL100K_TOKENS, &mut out).unwrap(),
+            _ => super::encode(arguments, &gptbpe::bpe::vocabulary::R50K_TOKENS, &mut out).unwrap(),
+        };
+        out
+    }
+
+    #[test]
+    fn formats() {
+        assert_eq!(encode(Encoding::R50K, Format::Ids, "hello world"), b"31373 995\n");
+        assert_eq!(encode(Encoding::R50K, Format::Json, "hi"), b"{\"text\":\"hi\",\"count\":1,\"ids\":[5303]}\n");
+        assert_eq!(
+            String::from_utf8(encode(Encoding::R50K, Format::Jsonl, "hello world\r\nbye")).unwrap(),
+            "{\"text\":\"hello world\",\"count\":2,\"ids\":[31373,995]}\n{\"text\":\"bye\",\"count\":1,\"ids\":[16390]}\n"
+        );
+        assert_eq!(
+            String::from_utf8(encode(Encoding::R50K, Format::Tsv, "a\tb\\")).unwrap(),
+            "id\tstart\tend\ttext\n64\t0\t1\ta\n197\t1\t2\t\\t\n65\t2\t3\tb\n59\t3\t4\t\\\\\n"
+        );
+    }
+
+    /// Output of a `vocab` subcommand over the r50k vocabulary.
+    fn vocab(command: super::VocabCommand) -> String {
+        let mut out = vec![];
+        super::vocab(command, &gptbpe::bpe::vocabulary::R50K_TOKENS, &gptbpe::bpe::vocabulary::R50K_UNICODES, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn search() {
+        use super::VocabCommand;
+        use super::VocabSearch;
+        let search = |prefix: Option<&str>, regex: Option<&str>, limit: Option<usize>| {
+            vocab(VocabCommand::Search(VocabSearch { prefix: prefix.map(String::from), regex: regex.map(String::from), limit }))
+        };
+        assert_eq!(search(Some(" hello"), None, None), "23748\t\"Ġhello\"\t\" hello\"\n");
+        assert_eq!(search(Some(" hel"), Some("p$"), None), "1037\t\"Ġhelp\"\t\" help\"\n11727\t\"Ġhelicop\"\t\" helicop\"\n");
+        assert_eq!(search(None, Some("^ hell[a-z]*o$"), Some(1)), "23748\t\"Ġhello\"\t\" hello\"\n");
+        assert_eq!(vocab(VocabCommand::Get(super::VocabGet { ids: vec![31373] })), "31373\t\"hello\"\t\"hello\"\n");
+        assert_eq!(
+            vocab(VocabCommand::Find(super::VocabFind { unicode: true, string: "Ġhello".to_string() })),
+            "23748\t\"Ġhello\"\t\" hello\"\n"
+        );
+    }
 
-    for line in stdin().lines() {
-        let data = line.unwrap();
-        let e = bpe::encode(data.as_bytes(), &crate::bpe::vocabulary::R50K_TOKENS);
-        println!("[INFO][ENCODE]: {:?} -> {:?}", data, e);
+    #[test]
+    fn binary() {
+        // Ids are two bytes wide for 16 bit vocabularies and four bytes wide otherwise.
+        assert_eq!(encode(Encoding::R50K, Format::Binary, "hi"), 5303u16.to_le_bytes());
+        assert_eq!(encode(Encoding::CL100K, Format::Binary, " mouths"), 65609u32.to_le_bytes());
     }
 }
@@ -0,0 +1,150 @@
+//! JSON-RPC 2.0 over newline-delimited streams, exposing the [service](crate::service) operations as methods.
+//!
+//! Every line is a request object or a batch array, and every answered request gets one response line.
+//! Params are the request objects of [handle](crate::service::handle). Notifications, requests without an `id`, are run but not answered.
+use serde_json::json;
+use serde_json::Value;
+use std::io::BufRead;
+use std::io::Write;
+
+/// Invalid JSON was received.
+const PARSE_ERROR: i64 = -32700;
+/// The JSON sent is not a valid request object.
+const INVALID_REQUEST: i64 = -32600;
+/// The method does not exist.
+const METHOD_NOT_FOUND: i64 = -32601;
+/// Invalid method parameters.
+const INVALID_PARAMS: i64 = -32602;
+
+/// An error response.
+fn error(id: Value, code: i64, message: impl Into<String>) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message.into() } })
+}
+
+/// Answers a request object.
+///
+/// ## Call
+/// ### Returns
+/// * the response, `None` for notifications.
+fn call(request: &Value) -> Option<Value> {
+    let Some(object) = request.as_object() else {
+        return Some(error(Value::Null, INVALID_REQUEST, "[ERROR]: The request must be a JSON object."));
+    };
+    let id = object.get("id").cloned();
+    let valid = matches!(id, None | Some(Value::Null | Value::Number(_) | Value::String(_)))
+        && object.get("jsonrpc").and_then(Value::as_str) == Some("2.0");
+    let (Some(method), true) = (object.get("method").and_then(Value::as_str), valid) else {
+        return Some(error(id.unwrap_or_default(), INVALID_REQUEST, "[ERROR]: Invalid JSON-RPC 2.0 request."));
+    };
+
+    let response = match (crate::service::OPERATIONS.contains(&method), object.get("params")) {
+        (false, _) => Err((METHOD_NOT_FOUND, format!("[ERROR]: Unknown method '{method}'."))),
+        (true, Some(params)) => crate::service::handle(method, params).map_err(|message| (INVALID_PARAMS, message)),
+        (true, None) => Err((INVALID_PARAMS, "[ERROR]: Missing params.".to_string())),
+    };
+    let id = id?;
+    Some(match response {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err((code, message)) => error(id, code, message),
+    })
+}
+
+/// Answers a line: a request object or a batch of them.
+///
+/// ## Answer
+/// ### Returns
+/// * the response line, `None` when nothing is answered.
+pub fn answer(line: &str) -> Option<Value> {
+    match serde_json::from_str::<Value>(line) {
+        Err(message) => Some(error(Value::Null, PARSE_ERROR, format!("[ERROR]: Invalid JSON: {message}"))),
+        Ok(Value::Array(batch)) if batch.is_empty() => Some(error(Value::Null, INVALID_REQUEST, "[ERROR]: Empty batch.")),
+        Ok(Value::Array(batch)) => {
+            let responses: Vec<Value> = batch.iter().filter_map(call).collect();
+            (!responses.is_empty()).then_some(Value::Array(responses))
+        }
+        Ok(request) => call(&request),
+    }
+}
+
+/// Answers JSON-RPC requests line by line until the input ends, flushing every response.
+///
+/// Blank lines are skipped and lines that are not UTF-8 get a parse error, the session going on.
+/// Vocabularies load once, on the first request using them, and stay loaded.
+///
+/// ## Serve
+/// ### Arguments
+/// * `input` - newline-delimited requests.
+/// * `output` - where newline-delimited responses are written.
+///
+/// ### Returns
+/// * the first input or output error.
+pub fn serve(mut input: impl BufRead, mut output: impl Write) -> std::io::Result<()> {
+    let mut line = vec![];
+    loop {
+        line.clear();
+        if input.read_until(b'\n', &mut line)? == 0 {
+            return Ok(());
+        };
+        let response = match std::str::from_utf8(&line) {
+            Ok(line) if line.trim().is_empty() => continue,
+            Ok(line) => answer(line),
+            Err(message) => Some(error(Value::Null, PARSE_ERROR, format!("[ERROR]: Invalid UTF-8: {message}"))),
+        };
+        if let Some(response) = response {
+            writeln!(output, "{response}")?;
+            output.flush()?;
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    #[test]
+    fn session() {
+        let input = [
+            r#"{"jsonrpc": "2.0", "id": 1, "method": "encode", "params": {"text": "hello world", "encoding": "cl100k"}}"#,
+            "",
+            r#"{"jsonrpc": "2.0", "method": "count", "params": {"text": "unanswered"}}"#,
+            r#"{"jsonrpc": "2.0", "id": "two", "method": "chunk", "params": {"text": "hello big world", "size": 2}}"#,
+        ]
+        .join("\n");
+        let mut output = vec![];
+        super::serve(input.as_bytes(), &mut output).unwrap();
+        let responses: Vec<serde_json::Value> = output.split(|byte| *byte == b'\n').filter(|line| !line.is_empty()).map(|line| serde_json::from_slice(line).unwrap()).collect();
+        assert_eq!(responses, [
+            json!({ "jsonrpc": "2.0", "id": 1, "result": { "ids": [15339, 1917], "count": 2 } }),
+            json!({ "jsonrpc": "2.0", "id": "two", "result": { "chunks": [
+                { "text": "hello big", "tokens": [31373, 1263], "start": 0, "end": 9 },
+                { "text": " world", "tokens": [995], "start": 9, "end": 15 },
+            ] } }),
+        ]);
+    }
+
+    #[test]
+    fn invalid_utf8() {
+        let mut input = b"{\"jsonrpc\": \"2.0\", \"id\": 1, \"method\": \"count\", \"params\": {\"text\": \"\xff\"}}\r\n".to_vec();
+        input.extend_from_slice(br#"{"jsonrpc": "2.0", "id": 2, "method": "count", "params": {"text": "a b"}}"#);
+        let mut output = vec![];
+        super::serve(input.as_slice(), &mut output).unwrap();
+        let responses: Vec<serde_json::Value> = output.split(|byte| *byte == b'\n').filter(|line| !line.is_empty()).map(|line| serde_json::from_slice(line).unwrap()).collect();
+        assert_eq!(responses.len(), 2);
+        assert_eq!((responses[0]["id"].clone(), responses[0]["error"]["code"].clone()), (json!(null), json!(-32700)));
+        assert_eq!(responses[1], json!({ "jsonrpc": "2.0", "id": 2, "result": { "count": 2 } }));
+    }
+
+    #[test]
+    fn errors() {
+        let code = |line: &str| super::answer(line).unwrap()["error"]["code"].clone();
+        assert_eq!(code("not json"), -32700);
+        assert_eq!(code(r#"{"id": 1, "method": "count", "params": {"text": "x"}}"#), -32600);
+        assert_eq!(code(r#"{"jsonrpc": "2.0", "id": 1, "method": "nothing", "params": {}}"#), -32601);
+        assert_eq!(code(r#"{"jsonrpc": "2.0", "id": 1, "method": "chunk", "params": {"text": "x", "size": 0}}"#), -32602);
+        assert_eq!(code("[]"), -32600);
+
+        let batch = super::answer(r#"[{"jsonrpc": "2.0", "id": 1, "method": "count", "params": {"text": "a b"}}, 5]"#).unwrap();
+        assert_eq!(batch[0]["result"], json!({ "count": 2 }));
+        assert_eq!(batch[1]["error"]["code"], -32600);
+    }
+}
@@ -0,0 +1,295 @@
+//! Minimal HTTP/1.1 server exposing the [service](crate::service) operations as `POST /<operation>` routes.
+use serde_json::json;
+use serde_json::Value;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Read;
+use std::io::Write;
+use std::net::TcpListener;
+use std::net::TcpStream;
+use std::time::Duration;
+
+/// Largest request body accepted, in bytes.
+///
+/// ## Maximum body
+const MAXIMUM_BODY: usize = 64 * 1024 * 1024;
+
+/// Longest request line or header line accepted, in bytes.
+///
+/// ## Maximum line
+const MAXIMUM_LINE: usize = 8 * 1024;
+
+/// Largest request line and headers accepted together, in bytes.
+///
+/// ## Maximum head
+const MAXIMUM_HEAD: usize = 64 * 1024;
+
+/// Time a client has to send each part of its request.
+///
+/// ## Timeout
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// An HTTP request line and body.
+///
+/// ## Request
+struct Request {
+    method: String,
+    path: String,
+    body: Vec<u8>,
+}
+
+/// Serves HTTP requests from a listener, one thread per connection, until accepting fails.
+///
+/// `POST /encode`, `/decode`, `/count`, `/truncate` and `/chunk` take and return JSON objects,
+/// see [handle](crate::service::handle). `GET /health` answers `{"status": "ok"}`.
+/// Errors answer `{"error": message}` with a `4xx` status. Connections close after one response.
+///
+/// ## Serve
+/// ### Arguments
+/// * `listener` - a bound listener.
+///
+/// ### Returns
+/// * the accept error that stopped the server.
+pub fn serve(listener: TcpListener) -> std::io::Result<()> {
+    for stream in listener.incoming() {
+        let stream = stream?;
+        std::thread::spawn(move || {
+            if let Err(error) = connection(stream) {
+                eprintln!("[ERROR]: {error}");
+            };
+        });
+    }
+    Ok(())
+}
+
+/// Reads one request from a connection and writes its response.
+///
+/// ## Connection
+fn connection(stream: TcpStream) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    let mut writer = stream.try_clone()?;
+    let mut reader = BufReader::new(stream);
+    let (status, body) = match read(&mut reader, &mut writer)? {
+        Ok(request) => route(&request),
+        Err(status) => (status, json!({ "error": reason(status) })),
+    };
+
+    let body = body.to_string();
+    write!(
+        writer,
+        "HTTP/1.1 {status} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        reason(status),
+        body.len(),
+    )?;
+    writer.flush()
+}
+
+/// Reads a line of the request head, line break included, empty at the end of the stream.
+///
+/// ## Line
+/// ### Arguments
+/// * `reader` - the connection.
+/// * `head` - bytes of the head left to read, the line length is taken off it.
+///
+/// ### Returns
+/// * the line, `None` when it is longer than [maximum line](MAXIMUM_LINE) or what is left of the head.
+fn line(reader: &mut impl BufRead, head: &mut usize) -> std::io::Result<Option<String>> {
+    let limit = MAXIMUM_LINE.min(*head);
+    let mut line = vec![];
+    reader.by_ref().take(limit as u64).read_until(b'\n', &mut line)?;
+    if line.len() == limit && !line.ends_with(b"\n") {
+        return Ok(None);
+    };
+    *head -= line.len();
+    Ok(Some(String::from_utf8_lossy(&line).into_owned()))
+}
+
+/// Reads the request line, headers and body of a request.
+///
+/// The head is read line by line within [maximum line](MAXIMUM_LINE) and [maximum head](MAXIMUM_HEAD),
+/// and the body grows as it arrives rather than being allocated at its announced length.
+///
+/// ## Read
+/// ### Returns
+/// * the request, or the status to reject it with.
+fn read(reader: &mut impl BufRead, writer: &mut impl Write) -> std::io::Result<Result<Request, u16>> {
+    let mut head = MAXIMUM_HEAD;
+    let Some(start) = line(reader, &mut head)? else {
+        return Ok(Err(414));
+    };
+    let mut parts = start.split_whitespace();
+    let (method, path) = match (parts.next(), parts.next(), parts.next()) {
+        (Some(method), Some(path), Some(version)) if version.starts_with("HTTP/1.") => (method.to_string(), path.to_string()),
+        _ => return Ok(Err(400)),
+    };
+
+    let mut length = 0;
+    let mut proceed = false;
+    loop {
+        let header = match line(reader, &mut head)? {
+            Some(header) if header.is_empty() => return Ok(Err(400)),
+            Some(header) => header,
+            None => return Ok(Err(431)),
+        };
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        };
+        let Some((name, value)) = header.split_once(':') else {
+            return Ok(Err(400));
+        };
+        let value = value.trim();
+        match name.trim().to_ascii_lowercase().as_str() {
+            "content-length" => match value.parse::<usize>() {
+                Ok(value) => length = value,
+                Err(_) => return Ok(Err(400)),
+            },
+            "transfer-encoding" => return Ok(Err(411)),
+            "expect" => proceed = value.eq_ignore_ascii_case("100-continue"),
+            _ => {}
+        };
+    }
+    if length > MAXIMUM_BODY {
+        return Ok(Err(413));
+    };
+    if proceed {
+        writer.write_all(b"HTTP/1.1 100 Continue\r\n\r\n")?;
+        writer.flush()?;
+    };
+
+    let mut body = vec![];
+    if reader.take(length as u64).read_to_end(&mut body)? < length {
+        return Err(std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "[ERROR]: Request body ended early."));
+    };
+    Ok(Ok(Request { method, path, body }))
+}
+
+/// Answers a request.
+///
+/// ## Route
+/// ### Returns
+/// * the status and JSON body of the response.
+fn route(request: &Request) -> (u16, Value) {
+    let path = request.path.split('?').next().unwrap_or_default();
+    if path == "/health" {
+        return match request.method.as_str() {
+            "GET" => (200, json!({ "status": "ok" })),
+            _ => (405, json!({ "error": reason(405) })),
+        };
+    };
+    let operation = path.trim_start_matches('/');
+    if !crate::service::OPERATIONS.contains(&operation) {
+        return (404, json!({ "error": reason(404) }));
+    };
+    if request.method != "POST" {
+        return (405, json!({ "error": reason(405) }));
+    };
+
+    let body: Value = match serde_json::from_slice(&request.body) {
+        Ok(body) => body,
+        Err(error) => return (400, json!({ "error": format!("[ERROR]: Invalid JSON: {error}") })),
+    };
+    match crate::service::handle(operation, &body) {
+        Ok(response) => (200, response),
+        Err(error) => (400, json!({ "error": error })),
+    }
+}
+
+/// Reason phrase of a status code.
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        411 => "Length Required",
+        413 => "Payload Too Large",
+        414 => "URI Too Long",
+        431 => "Request Header Fields Too Large",
+        _ => "Error",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use std::io::Read;
+    use std::io::Write;
+
+    /// Starts a server on a free loopback port.
+    fn start() -> std::net::SocketAddr {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        std::thread::spawn(move || super::serve(listener));
+        address
+    }
+
+    /// Sends a request and returns the status and JSON body of the response.
+    fn request(address: std::net::SocketAddr, method: &str, path: &str, body: &str) -> (u16, serde_json::Value) {
+        let mut stream = std::net::TcpStream::connect(address).unwrap();
+        write!(stream, "{method} {path} HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n\r\n{body}", body.len()).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        let status = head.split_whitespace().nth(1).unwrap().parse().unwrap();
+        (status, serde_json::from_str(body).unwrap())
+    }
+
+    #[test]
+    fn loopback() {
+        let address = start();
+
+        let (status, body) = request(address, "POST", "/encode", r#"{"text": "hello world 👋", "encoding": "cl100k"}"#);
+        assert_eq!((status, body), (200, json!({ "ids": [15339, 1917, 62904, 233], "count": 4 })));
+
+        let (status, body) = request(address, "POST", "/decode", r#"{"ids": [15339, 1917, 62904, 233], "encoding": "cl100k"}"#);
+        assert_eq!((status, body), (200, json!({ "text": "hello world 👋" })));
+
+        let (status, body) = request(address, "POST", "/count", r#"{"text": "hello world"}"#);
+        assert_eq!((status, body), (200, json!({ "count": 2 })));
+
+        let (status, body) = request(address, "POST", "/truncate", r#"{"text": "hello big world", "max_tokens": 2, "side": "start"}"#);
+        assert_eq!((status, body), (200, json!({ "text": " big world", "ids": [1263, 995], "count": 2 })));
+
+        let (status, body) = request(address, "GET", "/health", "");
+        assert_eq!((status, body), (200, json!({ "status": "ok" })));
+    }
+
+    /// Status a raw request is rejected with, `None` when it is read.
+    fn rejected(request: &[u8]) -> Option<u16> {
+        super::read(&mut std::io::Cursor::new(request), &mut vec![]).unwrap().err()
+    }
+
+    #[test]
+    fn limits() {
+        let long = "x".repeat(super::MAXIMUM_LINE);
+        assert_eq!(rejected(format!("GET /{long} HTTP/1.1\r\n\r\n").as_bytes()), Some(414));
+        assert_eq!(rejected(format!("GET / HTTP/1.1\r\nX-Long: {long}\r\n\r\n").as_bytes()), Some(431));
+
+        let header = format!("X-Header: {}\r\n", "x".repeat(1000));
+        let headers = header.repeat(super::MAXIMUM_HEAD / header.len() + 1);
+        assert_eq!(rejected(format!("GET / HTTP/1.1\r\n{headers}\r\n").as_bytes()), Some(431));
+        assert_eq!(rejected(format!("GET / HTTP/1.1\r\n{}\r\n", header.repeat(8)).as_bytes()), None);
+
+        // The announced length is not allocated up front, a short body is an error.
+        let request = format!("POST /count HTTP/1.1\r\nContent-Length: {}\r\n\r\n{{}}", super::MAXIMUM_BODY);
+        let read = super::read(&mut std::io::Cursor::new(request.as_bytes()), &mut vec![]);
+        assert!(read.is_err_and(|error| error.kind() == std::io::ErrorKind::UnexpectedEof));
+        assert_eq!(rejected(b"POST /count HTTP/1.1\r\nContent-Length: 67108865\r\n\r\n"), Some(413));
+    }
+
+    #[test]
+    fn errors() {
+        let address = start();
+
+        let (status, body) = request(address, "POST", "/decode", r#"{"ids": [99999]}"#);
+        assert_eq!(status, 400);
+        assert_eq!(body["error"], "[ERROR]: Token id 99999 is not in the vocabulary.");
+
+        assert_eq!(request(address, "POST", "/count", r#"{"text": 1}"#).0, 400);
+        assert_eq!(request(address, "POST", "/count", r#"{"text": "x", "encoding": "x"}"#).0, 400);
+        assert_eq!(request(address, "POST", "/count", "not json").0, 400);
+        assert_eq!(request(address, "GET", "/count", "").0, 405);
+        assert_eq!(request(address, "POST", "/nothing", "{}").0, 404);
+    }
+}
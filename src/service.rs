@@ -0,0 +1,121 @@
+//! Tokenization operations over JSON values, shared by the HTTP server and the JSON-RPC mode of the command line.
+//!
+//! Every operation takes a JSON object with an optional `encoding` field (`r50k` by default)
+//! and returns a JSON object, or an error message for the client.
+use crate::bpe;
+use crate::bpe::vocabulary::Encoding;
+use serde_json::json;
+use serde_json::Value;
+
+/// Names of the operations [handle](handle) knows.
+///
+/// ## Operations
+pub const OPERATIONS: [&str; 5] = ["encode", "decode", "count", "truncate", "chunk"];
+
+/// Reads the `encoding` field of a request.
+fn encoding(request: &Value) -> Result<Encoding, String> {
+    let encoding: Encoding = match request.get("encoding") {
+        None | Some(Value::Null) => Encoding::R50K,
+        Some(Value::String(name)) => name.parse()?,
+        Some(_) => return Err("[ERROR]: 'encoding' must be a string.".to_string()),
+    };
+    match encoding.available() {
+        true => Ok(encoding),
+        false => Err(format!("[ERROR]: Vocabulary file '{}' not found.", encoding.path())),
+    }
+}
+
+/// Reads a required string field of a request.
+fn string<'a>(request: &'a Value, field: &str) -> Result<&'a str, String> {
+    request
+        .get(field)
+        .and_then(Value::as_str)
+        .ok_or(format!("[ERROR]: '{field}' must be a string."))
+}
+
+/// Reads a required non-negative integer field of a request.
+fn integer(request: &Value, field: &str) -> Result<u64, String> {
+    request
+        .get(field)
+        .and_then(Value::as_u64)
+        .ok_or(format!("[ERROR]: '{field}' must be a non-negative integer."))
+}
+
+/// Reads an optional non-negative integer field of a request, `0` when missing.
+fn optional(request: &Value, field: &str) -> Result<usize, String> {
+    match request.get(field) {
+        None | Some(Value::Null) => Ok(0),
+        Some(_) => Ok(usize::try_from(integer(request, field)?).unwrap_or(usize::MAX)),
+    }
+}
+
+/// Runs a tokenization operation.
+///
+/// * `encode` - `{"text"}` to `{"ids", "count"}`.
+/// * `decode` - `{"ids"}` to `{"text"}`, invalid UTF-8 replaced by `U+FFFD`.
+/// * `count` - `{"text"}` to `{"count"}`.
+/// * `truncate` - `{"text", "max_tokens", "side"}` to `{"text", "ids", "count"}`, `side` is `end` by default.
+/// * `chunk` - `{"text", "size", "overlap", "slack", "structure"}` to `{"chunks": [{"text", "tokens", "start", "end"}]}`,
+///   `overlap` and `slack` are `0` by default, and `structure` (`markdown` or `code`) replaces them when given.
+///
+/// Vocabularies load once per process, on the first request using them.
+///
+/// ## Handle
+/// ### Arguments
+/// * `operation` - one of the [operations](OPERATIONS).
+/// * `request` - the request object.
+///
+/// ### Returns
+/// * the response object, or an error message.
+pub fn handle(operation: &str, request: &Value) -> Result<Value, String> {
+    if !request.is_object() {
+        return Err("[ERROR]: The request must be a JSON object.".to_string());
+    };
+    match operation {
+        "encode" => {
+            let ids = encoding(request)?.encode(string(request, "text")?.as_bytes());
+            Ok(json!({ "count": ids.len(), "ids": ids }))
+        }
+        "decode" => {
+            let ids = request
+                .get("ids")
+                .and_then(Value::as_array)
+                .and_then(|ids| ids.iter().map(|id| id.as_u64().and_then(|id| u32::try_from(id).ok())).collect::<Option<Vec<u32>>>())
+                .ok_or("[ERROR]: 'ids' must be an array of token ids.".to_string())?;
+            let bytes = encoding(request)?
+                .decode(&ids)
+                .map_err(|id| format!("[ERROR]: Token id {id} is not in the vocabulary."))?;
+            Ok(json!({ "text": String::from_utf8_lossy(&bytes) }))
+        }
+        "count" => {
+            let count = encoding(request)?.count(string(request, "text")?.as_bytes());
+            Ok(json!({ "count": count }))
+        }
+        "truncate" => {
+            let side: bpe::Side = match request.get("side") {
+                None | Some(Value::Null) => bpe::Side::End,
+                Some(Value::String(side)) => side.parse()?,
+                Some(_) => return Err("[ERROR]: 'side' must be a string.".to_string()),
+            };
+            let max_tokens = usize::try_from(integer(request, "max_tokens")?).unwrap_or(usize::MAX);
+            let (text, ids) = encoding(request)?.truncate(string(request, "text")?, max_tokens, side);
+            Ok(json!({ "text": text, "count": ids.len(), "ids": ids }))
+        }
+        "chunk" => {
+            let text = string(request, "text")?;
+            let size = usize::try_from(integer(request, "size")?).unwrap_or(usize::MAX);
+            let (overlap, slack) = (optional(request, "overlap")?, optional(request, "slack")?);
+            let chunks = match request.get("structure") {
+                None | Some(Value::Null) => encoding(request)?.chunk(text, size, overlap, slack)?,
+                Some(Value::String(format)) => encoding(request)?.structured(text, size, format.parse()?)?,
+                Some(_) => return Err("[ERROR]: 'structure' must be a string.".to_string()),
+            };
+            let chunks: Vec<Value> = chunks
+                .iter()
+                .map(|chunk| json!({ "text": chunk.text, "tokens": chunk.tokens, "start": chunk.range.start, "end": chunk.range.end }))
+                .collect();
+            Ok(json!({ "chunks": chunks }))
+        }
+        _ => Err(format!("[ERROR]: Unknown operation '{operation}'.")),
+    }
+}
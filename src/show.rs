@@ -0,0 +1,129 @@
+use std::ops::Range;
+
+/// Background colors cycled through, one per token, from the 256 color palette.
+///
+/// ## Palette
+const PALETTE: [u8; 5] = [153, 217, 186, 223, 183];
+
+/// Consecutive tokens printed as one piece of text, so a multibyte character split across tokens stays whole.
+///
+/// ## Segment
+struct Segment {
+    ids: Vec<u32>,
+    range: Range<usize>,
+}
+
+/// Groups tokens into segments ending on character boundaries.
+///
+/// ## Segments
+/// ### Arguments
+/// * `data` - the encoded bytes.
+/// * `tokens` - token ids with byte offsets, as the encoder returns them.
+///
+/// ### Returns
+/// * the segments, in order.
+fn segments(data: &[u8], tokens: &[(u32, usize, usize)]) -> Vec<Segment> {
+    let mut segments: Vec<Segment> = vec![];
+    let mut open = false;
+    for (id, start, end) in tokens {
+        match segments.last_mut() {
+            Some(segment) if open => {
+                segment.ids.push(*id);
+                segment.range.end = *end;
+            }
+            _ => segments.push(Segment { ids: vec![*id], range: *start..*end }),
+        };
+        let segment = segments.last().unwrap();
+        // Keep the segment open while it ends in an incomplete character.
+        open = matches!(std::str::from_utf8(&data[segment.range.clone()]), Err(error) if error.error_len().is_none());
+    }
+    segments
+}
+
+/// Renders text with its token boundaries visible.
+///
+/// With `color`, every token gets the next background color of the [palette](PALETTE) and
+/// line breaks are drawn as `↵` so tokens made of them stay visible.
+/// Without it, tokens are separated by `|`.
+///
+/// ## Render
+/// ### Arguments
+/// * `data` - the encoded bytes.
+/// * `tokens` - token ids with byte offsets, as the encoder returns them.
+/// * `ids` - whether to annotate every token with its id.
+/// * `color` - whether to use terminal colors.
+///
+/// ### Returns
+/// * the rendered text.
+pub fn render(data: &[u8], tokens: &[(u32, usize, usize)], ids: bool, color: bool) -> String {
+    let mut output = String::new();
+    for (index, segment) in segments(data, tokens).iter().enumerate() {
+        let text = String::from_utf8_lossy(&data[segment.range.clone()]);
+        let annotation = match ids {
+            true => {
+                let ids: Vec<String> = segment.ids.iter().map(|id| id.to_string()).collect();
+                format!("[{}]", ids.join(" "))
+            }
+            false => String::new(),
+        };
+
+        if !color {
+            if index > 0 {
+                output.push('|');
+            };
+            output.push_str(&text);
+            output.push_str(&annotation);
+            continue;
+        };
+
+        let background = PALETTE[index % PALETTE.len()];
+        for (line, piece) in text.split('\n').enumerate() {
+            if line > 0 {
+                output.push_str(&format!("\x1b[30;48;5;{background}m↵\x1b[0m\n"));
+            };
+            if !piece.is_empty() {
+                output.push_str(&format!("\x1b[30;48;5;{background}m{piece}\x1b[0m"));
+            };
+        }
+        if ids {
+            output.push_str(&format!("\x1b[2m{annotation}\x1b[0m"));
+        };
+    }
+    if !output.ends_with('\n') {
+        output.push('\n');
+    };
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    /// Token ids and byte offsets of a text, widened as the `show` subcommand passes them.
+    fn offsets(text: &str) -> Vec<(u32, usize, usize)> {
+        gptbpe::bpe::encode_with_offsets(text.as_bytes(), &gptbpe::bpe::vocabulary::R50K_TOKENS)
+            .into_iter()
+            .map(|(id, start, end)| (u32::from(id), start, end))
+            .collect()
+    }
+
+    #[test]
+    fn plain() {
+        let text = "hi 👋 you";
+        // 👋 spans two tokens and is printed whole, with both ids.
+        assert_eq!(super::render(text.as_bytes(), &offsets(text), true, false), "hi[5303]| 👋[50169 233]| you[345]\n");
+        assert_eq!(super::render(text.as_bytes(), &offsets(text), false, false), "hi| 👋| you\n");
+        assert_eq!(super::render(b"", &[], false, false), "\n");
+    }
+
+    #[test]
+    fn colored() {
+        let text = "a\nb";
+        assert_eq!(
+            super::render(text.as_bytes(), &offsets(text), false, true),
+            "\x1b[30;48;5;153ma\x1b[0m\x1b[30;48;5;217m↵\x1b[0m\n\x1b[30;48;5;186mb\x1b[0m\n"
+        );
+        assert_eq!(
+            super::render(b"hi", &offsets("hi"), true, true),
+            "\x1b[30;48;5;153mhi\x1b[0m\x1b[2m[5303]\x1b[0m\n"
+        );
+    }
+}
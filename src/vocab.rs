@@ -0,0 +1,83 @@
+use gptbpe::bpe;
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::sync::LazyLock;
+
+/// Escapes bytes as a quoted string: printable characters as is, invalid UTF-8 bytes as `\xNN`.
+fn escape(bytes: &[u8]) -> String {
+    let mut escaped = String::from('"');
+    for chunk in bytes.utf8_chunks() {
+        escaped.extend(chunk.valid().chars().flat_map(char::escape_debug));
+        for byte in chunk.invalid() {
+            escaped.push_str(&format!("\\x{byte:02x}"));
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+/// Writes a vocabulary entry: its id, GPT unicode form and decoded bytes, separated by tabs.
+///
+/// ## Entry
+/// ### Arguments
+/// * `out` - the output.
+/// * `token` - the token id.
+/// * `unicodes` - the GPT unicode form of the token.
+pub fn entry<D: std::fmt::Display>(out: &mut impl Write, token: &D, unicodes: &[u8]) -> std::io::Result<()> {
+    let form = String::from_utf8_lossy(unicodes);
+    writeln!(out, "{token}\t{form:?}\t{}", escape(&bpe::bytes(unicodes)))
+}
+
+/// Tokens whose decoded bytes start with a prefix, in id order.
+///
+/// The GPT unicode scheme maps bytes one by one, so the prefix is looked up as a range of the sorted tokens.
+///
+/// ## Prefixed
+/// ### Arguments
+/// * `tokens` - the vocabulary tokens.
+/// * `prefix` - the decoded prefix.
+///
+/// ### Returns
+/// * the matching token ids and GPT unicode forms.
+pub fn prefixed<'a, D: std::clone::Clone + std::cmp::Ord>(
+    tokens: &'a LazyLock<BTreeMap<Vec<u8>, D>>,
+    prefix: &[u8],
+) -> Vec<(D, &'a [u8])> {
+    let prefix = bpe::unicodes(prefix).concat();
+    let mut matches: Vec<(D, &[u8])> = tokens
+        .range(prefix.clone()..)
+        .take_while(|(unicodes, _)| unicodes.starts_with(&prefix))
+        .map(|(unicodes, token)| (token.clone(), unicodes.as_slice()))
+        .collect();
+    matches.sort_by(|a, b| a.0.cmp(&b.0));
+    matches
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn escape() {
+        assert_eq!(super::escape(b" hello"), "\" hello\"");
+        assert_eq!(super::escape(b"a\tb\"\n"), "\"a\\tb\\\"\\n\"");
+        assert_eq!(super::escape(b" \xf0\x9f\x91"), "\" \\xf0\\x9f\\x91\"");
+    }
+
+    #[test]
+    fn entry() {
+        let mut out = vec![];
+        super::entry(&mut out, &50169u16, &gptbpe::bpe::unicodes(b" \xf0\x9f\x91").concat()).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "50169\t\"ĠðŁĳ\"\t\" \\xf0\\x9f\\x91\"\n");
+    }
+
+    #[test]
+    fn prefixed() {
+        let tokens = &gptbpe::bpe::vocabulary::R50K_TOKENS;
+        let matches = super::prefixed(tokens, b" hello");
+        assert_eq!(matches, vec![(23748, "Ġhello".as_bytes())]);
+
+        let matches = super::prefixed(tokens, b" hel");
+        assert!(matches.len() > 1 && matches.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        assert!(matches.iter().all(|(_, form)| form.starts_with("Ġhel".as_bytes())));
+        assert!(super::prefixed(tokens, b" \xff\xfe\xfd").is_empty());
+    }
+}